@@ -0,0 +1,78 @@
+use crate::QueryScratch;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Results of a batch of queries in a flattened (CSR-style) layout: the hits for query `i` are
+/// `ids[offsets[i]..offsets[i + 1]]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BatchResults {
+    pub offsets: Vec<usize>,
+    pub ids: Vec<usize>,
+}
+
+impl BatchResults {
+    /// The number of queries in the batch.
+    pub fn len(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The hits for the query at position `i` in the batch.
+    pub fn get(&self, i: usize) -> &[usize] {
+        &self.ids[self.offsets[i]..self.offsets[i + 1]]
+    }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = &'a [usize]> + 'a {
+        self.offsets.windows(2).map(move |w| &self.ids[w[0]..w[1]])
+    }
+}
+
+// run each query into a shared scratch, appending its results to the flattened output
+pub(crate) fn batch<Q, F>(queries: &[Q], query: F) -> BatchResults
+where
+    F: Fn(&Q, &mut QueryScratch),
+{
+    let mut scratch = QueryScratch::new();
+    let mut out = BatchResults { offsets: Vec::with_capacity(queries.len() + 1), ids: Vec::new() };
+    out.offsets.push(0);
+
+    for q in queries {
+        query(q, &mut scratch);
+        out.ids.extend_from_slice(scratch.results());
+        out.offsets.push(out.ids.len());
+    }
+
+    out
+}
+
+// split the queries into chunks, run each chunk as its own batch on the rayon thread pool, then
+// stitch the per-chunk outputs back together in order
+#[cfg(feature = "rayon")]
+pub(crate) fn par_batch<Q, F>(queries: &[Q], query: F) -> BatchResults
+where
+    Q: Sync,
+    F: Fn(&Q, &mut QueryScratch) + Sync,
+{
+    let chunk_size = 1.max(queries.len() / (4 * rayon::current_num_threads()));
+    let chunks: Vec<BatchResults> =
+        queries.par_chunks(chunk_size).map(|chunk| batch(chunk, &query)).collect();
+
+    let num_ids = chunks.iter().map(|c| c.ids.len()).sum();
+    let mut out = BatchResults {
+        offsets: Vec::with_capacity(queries.len() + 1),
+        ids: Vec::with_capacity(num_ids),
+    };
+    out.offsets.push(0);
+
+    for chunk in chunks {
+        let base = out.ids.len();
+        out.offsets.extend(chunk.offsets[1..].iter().map(|o| base + o));
+        out.ids.extend(chunk.ids);
+    }
+
+    out
+}
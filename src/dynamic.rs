@@ -0,0 +1,271 @@
+// shared machinery for the dynamic (insert/remove) wrappers around the static indexes, using the
+// Bentley-Saxe logarithmic method: new items go into a small unindexed buffer, and whenever it
+// fills up, it's merged with every full level below the first empty one into a single static index
+// that takes that empty level's place, so each item is rebuilt O(log n) times over its lifetime.
+// Removals are handled with per-level tombstones; removed items are dropped during the next merge
+// that touches them, or sooner if most of their level has been removed, in which case the level is
+// rebuilt from what's left on the spot.
+
+use core::ops::ControlFlow;
+
+use crate::util::BitSet;
+use crate::QueryScratch;
+
+pub(crate) const DEFAULT_BUFFER_SIZE: usize = 256;
+
+pub(crate) trait StaticIndex: Sized {
+    type Item: Copy;
+
+    fn build<I: Iterator<Item = Self::Item>>(items: I, node_size: usize) -> Self;
+
+    // call `f` with the local (build-order) id and value of every item in the index
+    fn for_each_item<F: FnMut(usize, Self::Item)>(&self, f: F);
+}
+
+#[derive(Clone)]
+pub(crate) struct Level<B> {
+    bush: B,
+    // maps the level's local ids back to the dynamic index's ids
+    ids: Vec<usize>,
+    // local ids of removed items
+    dead: BitSet,
+}
+
+// where an item is stored: in a level under a local id, in the buffer, or nowhere once it's been
+// removed. There's one of these for every id ever handed out, so it's kept to 8 bytes
+#[derive(Clone, Copy, PartialEq)]
+struct Location {
+    level: u8,
+    local: u32,
+}
+
+impl Location {
+    const BUFFER: Location = Location { level: u8::MAX, local: 0 };
+    const REMOVED: Location = Location { level: u8::MAX - 1, local: 0 };
+}
+
+#[derive(Clone)]
+pub(crate) struct LogStructured<B: StaticIndex> {
+    node_size: usize,
+    buffer_size: usize,
+    buffer: Vec<(usize, B::Item)>,
+    levels: Vec<Option<Level<B>>>,
+    // indexed by id; ids are handed out sequentially and never reused
+    locations: Vec<Location>,
+    len: usize,
+}
+
+impl<B: StaticIndex> LogStructured<B> {
+    pub fn new(node_size: usize, buffer_size: usize) -> LogStructured<B> {
+        LogStructured {
+            node_size,
+            buffer_size: buffer_size.max(1),
+            buffer: Vec::new(),
+            levels: Vec::new(),
+            locations: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn insert(&mut self, item: B::Item) -> usize {
+        let id = self.locations.len();
+        self.locations.push(Location::BUFFER);
+        self.len += 1;
+
+        self.buffer.push((id, item));
+        if self.buffer.len() >= self.buffer_size {
+            self.flush();
+        }
+
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) -> bool {
+        let location = match self.locations.get(id) {
+            Some(&location) if location != Location::REMOVED => location,
+            _ => return false,
+        };
+        self.locations[id] = Location::REMOVED;
+        self.len -= 1;
+
+        if location == Location::BUFFER {
+            // items still in the buffer can just be dropped
+            if let Some(i) = self.buffer.iter().position(|(buffered, _)| *buffered == id) {
+                self.buffer.swap_remove(i);
+            }
+            return true;
+        }
+
+        // indexed ones stay behind as tombstones, until they make up most of their level
+        let k = location.level as usize;
+        let level = self.levels[k].as_mut().expect("location points at an empty level");
+        level.dead.insert(location.local as usize);
+        if 2 * level.dead.len() > level.ids.len() {
+            let level = self.levels[k].take().unwrap();
+            let (ids, items) = live_items(level);
+            self.levels[k] = self.build_level(k, ids, items);
+            self.trim_levels();
+        }
+        true
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        matches!(self.locations.get(id), Some(&location) if location != Location::REMOVED)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // the number of items held in the buffer and levels, including tombstones
+    #[cfg(test)]
+    pub fn num_stored(&self) -> usize {
+        self.buffer.len() + self.levels.iter().flatten().map(|level| level.ids.len()).sum::<usize>()
+    }
+
+    // move the buffer and every level below the first empty one into a single new static index
+    fn flush(&mut self) {
+        let target = self.levels.iter().position(Option::is_none).unwrap_or(self.levels.len());
+        if target == self.levels.len() {
+            self.levels.push(None);
+        }
+
+        let mut ids = Vec::new();
+        let mut items = Vec::new();
+        for (id, item) in self.buffer.drain(..) {
+            ids.push(id);
+            items.push(item);
+        }
+
+        for level in self.levels[..target].iter_mut().filter_map(Option::take) {
+            let (level_ids, level_items) = live_items(level);
+            ids.extend(level_ids);
+            items.extend(level_items);
+        }
+
+        self.levels[target] = self.build_level(target, ids, items);
+        self.trim_levels();
+    }
+
+    // build a static index for level `k`, pointing the items' locations at it
+    fn build_level(&mut self, k: usize, ids: Vec<usize>, items: Vec<B::Item>) -> Option<Level<B>> {
+        if ids.is_empty() {
+            return None;
+        }
+
+        for (local, id) in ids.iter().enumerate() {
+            self.locations[*id] = Location { level: k as u8, local: local as u32 };
+        }
+        let bush = B::build(items.into_iter(), self.node_size);
+        Some(Level { bush, ids, dead: BitSet::default() })
+    }
+
+    fn trim_levels(&mut self) {
+        while let Some(None) = self.levels.last() {
+            self.levels.pop();
+        }
+    }
+
+    // run a query against the buffer (using `matches`) and every level (using `search`), and
+    // merge the results, skipping anything that's been removed
+    pub fn query<'a, P, S, R>(&'a self, matches: P, search: S) -> impl Iterator<Item = usize> + 'a
+    where
+        P: Fn(&B::Item) -> bool + 'a,
+        S: Fn(&'a B) -> R + 'a,
+        R: Iterator<Item = usize> + 'a,
+    {
+        let buffered = self.buffer.iter().filter(move |(_, item)| matches(item)).map(|(id, _)| *id);
+        let indexed = self.levels.iter().flatten().flat_map(move |level| {
+            search(&level.bush)
+                .filter(move |local| !level.dead.contains(*local))
+                .map(move |local| level.ids[local])
+        });
+
+        buffered.chain(indexed)
+    }
+
+    // like `query`, but calls `visitor` with each result, using `visit` to walk each level
+    pub fn visit<R, P, V, F>(&self, matches: P, visit: V, mut visitor: F) -> ControlFlow<R>
+    where
+        P: Fn(&B::Item) -> bool,
+        V: Fn(&B, &mut dyn FnMut(usize) -> ControlFlow<R>) -> ControlFlow<R>,
+        F: FnMut(usize) -> ControlFlow<R>,
+    {
+        for (id, item) in self.buffer.iter() {
+            if matches(item) {
+                visitor(*id)?;
+            }
+        }
+
+        for level in self.levels.iter().flatten() {
+            visit(&level.bush, &mut |local| {
+                if level.dead.contains(local) {
+                    ControlFlow::Continue(())
+                } else {
+                    visitor(level.ids[local])
+                }
+            })?;
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    // like `visit`, but collects the results into a reusable scratch
+    pub fn visit_into<'s, P, V>(
+        &self,
+        scratch: &'s mut QueryScratch,
+        matches: P,
+        visit: V,
+    ) -> &'s [usize]
+    where
+        P: Fn(&B::Item) -> bool,
+        V: Fn(&B, &mut dyn FnMut(usize) -> ControlFlow<()>) -> ControlFlow<()>,
+    {
+        scratch.clear();
+        let results = &mut scratch.results;
+        let _ = self.visit(matches, visit, |id| {
+            results.push(id);
+            ControlFlow::Continue(())
+        });
+        &scratch.results
+    }
+
+    // count the results of a query, using `count` for levels without tombstones and falling back
+    // to `search` for the rest
+    pub fn count<'a, P, C, S, R>(&'a self, matches: P, count: C, search: S) -> usize
+    where
+        P: Fn(&B::Item) -> bool,
+        C: Fn(&B) -> usize,
+        S: Fn(&'a B) -> R,
+        R: Iterator<Item = usize> + 'a,
+    {
+        let buffered = self.buffer.iter().filter(|(_, item)| matches(item)).count();
+        let indexed: usize = self
+            .levels
+            .iter()
+            .flatten()
+            .map(|level| {
+                if level.dead.is_empty() {
+                    count(&level.bush)
+                } else {
+                    search(&level.bush).filter(|local| !level.dead.contains(*local)).count()
+                }
+            })
+            .sum();
+
+        buffered + indexed
+    }
+}
+
+// the ids and values of a level's items that haven't been removed
+fn live_items<B: StaticIndex>(level: Level<B>) -> (Vec<usize>, Vec<B::Item>) {
+    let mut ids = Vec::new();
+    let mut items = Vec::new();
+    level.bush.for_each_item(|local, item| {
+        if !level.dead.contains(local) {
+            ids.push(level.ids[local]);
+            items.push(item);
+        }
+    });
+    (ids, items)
+}
@@ -0,0 +1,140 @@
+use core::ops::Deref;
+
+use crate::flatbush::{upper_bound, AllowedNumber, FlatBush, FlatBushBuilder};
+
+/// An associative way of combining per-item values, with an identity element. Used to store a
+/// summary of each subtree alongside its box in an `AggregateFlatBush`.
+pub trait Monoid {
+    type Value: Clone;
+
+    fn identity(&self) -> Self::Value;
+
+    fn combine(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+/// A `Monoid` built from an identity value and a combining function.
+#[derive(Clone)]
+pub struct FnMonoid<V, F> {
+    identity: V,
+    combine: F,
+}
+
+impl<V: Clone, F: Fn(&V, &V) -> V> FnMonoid<V, F> {
+    pub fn new(identity: V, combine: F) -> FnMonoid<V, F> {
+        FnMonoid { identity, combine }
+    }
+}
+
+impl<V: Clone, F: Fn(&V, &V) -> V> Monoid for FnMonoid<V, F> {
+    type Value = V;
+
+    fn identity(&self) -> V {
+        self.identity.clone()
+    }
+
+    fn combine(&self, a: &V, b: &V) -> V {
+        (self.combine)(a, b)
+    }
+}
+
+/// A `FlatBush` that also stores, for every node, the combined value of all the items beneath
+/// it. Dereferences to the underlying `FlatBush`, so all the usual queries are available too.
+#[derive(Clone)]
+pub struct AggregateFlatBush<T: AllowedNumber, M: Monoid> {
+    bush: FlatBush<T>,
+    monoid: M,
+    // one value per box, in the same order as `bush.boxes`
+    aggregates: Vec<M::Value>,
+}
+
+impl<T: AllowedNumber> FlatBushBuilder<T> {
+    /// Build the index along with per-node aggregates of `values`, which must hold one value per
+    /// added item, in the order the items were added.
+    pub fn finish_with_aggregate<M: Monoid>(
+        self,
+        monoid: M,
+        values: Vec<M::Value>,
+    ) -> AggregateFlatBush<T, M> {
+        assert_eq!(values.len(), self.boxes.len() >> 2, "expected one value per item");
+
+        let bush = self.finish();
+        let num_nodes = bush.boxes.len() >> 2;
+
+        let mut aggregates: Vec<M::Value> = Vec::with_capacity(num_nodes);
+        for i in 0..bush.num_items {
+            aggregates.push(values[bush.indices.get(i) as usize].clone());
+        }
+
+        // nodes are stored bottom-up, so each node's children are always filled in before it
+        for i in bush.num_items..num_nodes {
+            let mut value = monoid.identity();
+            for pos in bush.child_range(4 * i).step_by(4) {
+                value = monoid.combine(&value, &aggregates[pos >> 2]);
+            }
+            aggregates.push(value);
+        }
+
+        AggregateFlatBush { bush, monoid, aggregates }
+    }
+}
+
+impl<T: AllowedNumber, M: Monoid> AggregateFlatBush<T, M> {
+    /// The combined value of every item whose box intersects the given range.
+    pub fn aggregate_range(&self, min_x: T, min_y: T, max_x: T, max_y: T) -> M::Value {
+        let mut value = self.monoid.identity();
+        self.aggregate_node(self.bush.boxes.len() - 4, [min_x, min_y, max_x, max_y], &mut value);
+        value
+    }
+
+    /// The combined value of every item in the index.
+    pub fn total(&self) -> &M::Value {
+        &self.aggregates[self.aggregates.len() - 1]
+    }
+
+    pub fn monoid(&self) -> &M {
+        &self.monoid
+    }
+
+    fn aggregate_node(&self, node_index: usize, query: [T; 4], value: &mut M::Value) {
+        let [min_x, min_y, max_x, max_y] = query;
+        let bush = &self.bush;
+
+        // find the end index of the node
+        let end =
+            (node_index + bush.node_size * 4).min(upper_bound(node_index, &bush.level_bounds));
+
+        for pos in (node_index..end).step_by(4) {
+            let node_box = &bush.boxes[pos..(pos + 4)];
+
+            // skip nodes that don't intersect the query
+            if max_x < node_box[0]
+                || max_y < node_box[1]
+                || min_x > node_box[2]
+                || min_y > node_box[3]
+            {
+                continue;
+            }
+
+            // items that intersect, and nodes that are entirely inside the query, contribute
+            // their whole aggregate; everything else needs a closer look
+            if node_index < bush.num_items * 4
+                || (min_x <= node_box[0]
+                    && min_y <= node_box[1]
+                    && max_x >= node_box[2]
+                    && max_y >= node_box[3])
+            {
+                *value = self.monoid.combine(value, &self.aggregates[pos >> 2]);
+            } else {
+                self.aggregate_node(bush.indices.get(pos >> 2) as usize, query, value);
+            }
+        }
+    }
+}
+
+impl<T: AllowedNumber, M: Monoid> Deref for AggregateFlatBush<T, M> {
+    type Target = FlatBush<T>;
+
+    fn deref(&self) -> &FlatBush<T> {
+        &self.bush
+    }
+}
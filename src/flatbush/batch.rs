@@ -0,0 +1,24 @@
+use crate::batch::batch;
+#[cfg(feature = "rayon")]
+use crate::batch::par_batch;
+use crate::flatbush::{AllowedNumber, FlatBush};
+use crate::BatchResults;
+
+impl<T: AllowedNumber> FlatBush<T> {
+    /// Run `search_range` for each `[min_x, min_y, max_x, max_y]` query.
+    pub fn search_range_batch(&self, queries: &[[T; 4]]) -> BatchResults {
+        batch(queries, |q, scratch| {
+            self.search_range_with(q[0], q[1], q[2], q[3], scratch);
+        })
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T: AllowedNumber + Send + Sync> FlatBush<T> {
+    /// Like `search_range_batch`, but spreads the queries across the rayon thread pool.
+    pub fn par_search_range_batch(&self, queries: &[[T; 4]]) -> BatchResults {
+        par_batch(queries, |q, scratch| {
+            self.search_range_with(q[0], q[1], q[2], q[3], scratch);
+        })
+    }
+}
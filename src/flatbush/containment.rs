@@ -0,0 +1,99 @@
+use core::cmp::Ordering;
+
+use crate::flatbush::{AllowedNumber, FlatBush};
+
+impl<T: AllowedNumber> FlatBush<T> {
+    /// Search for items whose boxes lie entirely inside the query box.
+    pub fn search_contained_in<'a>(
+        &'a self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let intersects =
+            move |b: &[T]| max_x >= b[0] && max_y >= b[1] && min_x <= b[2] && min_y <= b[3];
+        let contained =
+            move |b: &[T]| min_x <= b[0] && min_y <= b[1] && max_x >= b[2] && max_y >= b[3];
+
+        // box positions still to visit, and whether they're known to be inside the query. A node
+        // that only partly overlaps the query can still have children inside it, so nodes are
+        // pruned on intersection; once a node is inside, so is everything under it
+        let mut stack: Vec<(usize, bool)> = Vec::new();
+        let root = self.boxes.len() - 4;
+        let root_box = &self.boxes[root..(root + 4)];
+        if intersects(root_box) {
+            stack.push((root, contained(root_box)));
+        }
+
+        std::iter::from_fn(move || {
+            while let Some((pos, inside)) = stack.pop() {
+                if pos < self.num_items * 4 {
+                    let id = self.indices.get(pos >> 2) as usize;
+                    if inside && !self.deleted.contains(id) {
+                        return Some(id);
+                    }
+                    continue;
+                }
+
+                for child in self.child_range(pos).step_by(4) {
+                    if inside {
+                        stack.push((child, true));
+                        continue;
+                    }
+                    let child_box = &self.boxes[child..(child + 4)];
+                    if intersects(child_box) {
+                        stack.push((child, contained(child_box)));
+                    }
+                }
+            }
+            None
+        })
+    }
+
+    /// Search for items whose boxes entirely contain the query box.
+    pub fn search_containing<'a>(
+        &'a self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+    ) -> impl Iterator<Item = usize> + 'a {
+        // a node's box contains all of its children's, so any node that doesn't contain the query
+        // can't have children that do
+        let contains = move |node_box: &[T]| {
+            node_box[0] <= min_x
+                && node_box[1] <= min_y
+                && node_box[2] >= max_x
+                && node_box[3] >= max_y
+        };
+        self.search(contains, contains).map(|(id, _)| id)
+    }
+
+    /// Search for items whose boxes contain the point `(x, y)`, edges included.
+    pub fn search_point<'a>(&'a self, x: T, y: T) -> impl Iterator<Item = usize> + 'a {
+        let contains = move |node_box: &[T]| {
+            node_box[0] <= x && node_box[1] <= y && node_box[2] >= x && node_box[3] >= y
+        };
+        self.search(contains, contains).map(|(id, _)| id)
+    }
+
+    /// Like `search_point`, but returns the results ordered by the area of their boxes, smallest
+    /// (i.e. most specific) first.
+    pub fn search_point_by_area(&self, x: T, y: T) -> Vec<usize> {
+        let contains = move |node_box: &[T]| {
+            node_box[0] <= x && node_box[1] <= y && node_box[2] >= x && node_box[3] >= y
+        };
+        let mut results: Vec<(usize, f64)> = self
+            .search(contains, contains)
+            .map(|(id, pos)| {
+                let width: f64 = self.boxes[pos + 2].as_() - self.boxes[pos].as_();
+                let height: f64 = self.boxes[pos + 3].as_() - self.boxes[pos + 1].as_();
+                (id, width * height)
+            })
+            .collect();
+        results.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+
+        results.into_iter().map(|(id, _)| id).collect()
+    }
+}
@@ -0,0 +1,140 @@
+/// A curve through the index's bounds that orders items for packing into a `FlatBush`: items
+/// whose centers are close together along the curve end up in the same nodes. See
+/// `FlatBushBuilder::set_curve`.
+pub trait SpaceFillingCurve {
+    /// The position along the curve of the point `(x, y)`, given as its offset from the bottom-left
+    /// corner of bounds `width` × `height` in size, so both `x / width` and `y / height` are in
+    /// `[0, 1]`.
+    fn key(&self, x: f64, y: f64, width: f64, height: f64) -> u64;
+}
+
+/// The Hilbert curve on a 65536 × 65536 grid. The default, and what the JS Flatbush uses.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Hilbert;
+
+/// The Hilbert curve on a 2^32 × 2^32 grid, for datasets whose items are clustered so tightly
+/// that many of them would share a cell of the default grid.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Hilbert32;
+
+/// The Morton or Z-order curve, on a 2^32 × 2^32 grid. Cheaper to compute than the Hilbert
+/// curve, though it jumps around more, so nodes tend to be a little less compact.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Morton;
+
+impl SpaceFillingCurve for Hilbert {
+    fn key(&self, x: f64, y: f64, width: f64, height: f64) -> u64 {
+        // scaled by the grid size before dividing, so cells come out the same as in the JS
+        // Flatbush, and as indexes were packed before curves could be swapped out
+        let max = ((1 << 16) - 1) as f64;
+        hilbert((max * x / width).floor() as u32, (max * y / height).floor() as u32) as u64
+    }
+}
+
+impl SpaceFillingCurve for Hilbert32 {
+    fn key(&self, x: f64, y: f64, width: f64, height: f64) -> u64 {
+        let max = u32::MAX as f64;
+        hilbert_64((max * x / width).floor() as u64, (max * y / height).floor() as u64)
+    }
+}
+
+impl SpaceFillingCurve for Morton {
+    fn key(&self, x: f64, y: f64, width: f64, height: f64) -> u64 {
+        let max = u32::MAX as f64;
+        spread((max * x / width).floor() as u64) | (spread((max * y / height).floor() as u64) << 1)
+    }
+}
+
+// spread the low 32 bits of `v` out into the even bits of the result
+fn spread(mut v: u64) -> u64 {
+    v &= 0xFFFF_FFFF;
+    v = (v | (v << 16)) & 0x0000_FFFF_0000_FFFF;
+    v = (v | (v << 8)) & 0x00FF_00FF_00FF_00FF;
+    v = (v | (v << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    v = (v | (v << 2)) & 0x3333_3333_3333_3333;
+    v = (v | (v << 1)) & 0x5555_5555_5555_5555;
+    v
+}
+
+// the textbook bit-at-a-time Hilbert index, for 32-bit coordinates
+fn hilbert_64(mut x: u64, mut y: u64) -> u64 {
+    let n: u64 = 1 << 32;
+    let mut d = 0;
+
+    let mut s = n >> 1;
+    while s > 0 {
+        let rx = (x & s != 0) as u64;
+        let ry = (y & s != 0) as u64;
+        d += s * s * ((3 * rx) ^ ry);
+
+        // rotate the quadrant so the curve inside it has the right orientation
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s >>= 1;
+    }
+
+    d
+}
+
+// Fast Hilbert curve algorithm by http://threadlocalmutex.com/
+// Ported from C++ https://github.com/rawrunprotected/hilbert_curves (public domain)
+#[allow(non_snake_case)]
+pub(super) fn hilbert(x: u32, y: u32) -> u32 {
+    let mut a = x ^ y;
+    let mut b = 0xFFFF ^ a;
+    let mut c = 0xFFFF ^ (x | y);
+    let mut d = x & (y ^ 0xFFFF);
+
+    let mut A = a | (b >> 1);
+    let mut B = (a >> 1) ^ a;
+    let mut C = ((c >> 1) ^ (b & (d >> 1))) ^ c;
+    let mut D = ((a & (c >> 1)) ^ (d >> 1)) ^ d;
+
+    a = A;
+    b = B;
+    c = C;
+    d = D;
+    A = (a & (a >> 2)) ^ (b & (b >> 2));
+    B = (a & (b >> 2)) ^ (b & ((a ^ b) >> 2));
+    C ^= (a & (c >> 2)) ^ (b & (d >> 2));
+    D ^= (b & (c >> 2)) ^ ((a ^ b) & (d >> 2));
+
+    a = A;
+    b = B;
+    c = C;
+    d = D;
+    A = (a & (a >> 4)) ^ (b & (b >> 4));
+    B = (a & (b >> 4)) ^ (b & ((a ^ b) >> 4));
+    C ^= (a & (c >> 4)) ^ (b & (d >> 4));
+    D ^= (b & (c >> 4)) ^ ((a ^ b) & (d >> 4));
+
+    a = A;
+    b = B;
+    c = C;
+    d = D;
+    C ^= (a & (c >> 8)) ^ (b & (d >> 8));
+    D ^= (b & (c >> 8)) ^ ((a ^ b) & (d >> 8));
+
+    a = C ^ (C >> 1);
+    b = D ^ (D >> 1);
+
+    let mut i0 = x ^ y;
+    let mut i1 = b | (0xFFFF ^ (i0 | a));
+
+    i0 = (i0 | (i0 << 8)) & 0x00FF00FF;
+    i0 = (i0 | (i0 << 4)) & 0x0F0F0F0F;
+    i0 = (i0 | (i0 << 2)) & 0x33333333;
+    i0 = (i0 | (i0 << 1)) & 0x55555555;
+
+    i1 = (i1 | (i1 << 8)) & 0x00FF00FF;
+    i1 = (i1 | (i1 << 4)) & 0x0F0F0F0F;
+    i1 = (i1 | (i1 << 2)) & 0x33333333;
+    i1 = (i1 | (i1 << 1)) & 0x55555555;
+
+    (i1 << 1) | i0
+}
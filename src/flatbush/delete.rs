@@ -0,0 +1,97 @@
+use crate::flatbush::{level_bounds, pack_levels, AllowedNumber, FlatBush};
+use crate::util::{BitSet, IndexVec};
+
+impl<T: AllowedNumber> FlatBush<T> {
+    /// Mark the item with the given id as deleted, so that no query returns it. Returns whether
+    /// the item was newly deleted; ids that aren't in the index (including ones left out by an
+    /// earlier `compact`) are ignored. Takes constant time, unless the index came from `compact`
+    /// and was built without `set_id_lookup(true)`, in which case it takes linear time.
+    pub fn delete(&mut self, id: usize) -> bool {
+        if self.deleted.contains(id) || !self.has_id(id) {
+            return false;
+        }
+        self.deleted.insert(id)
+    }
+
+    pub fn is_deleted(&self, id: usize) -> bool {
+        self.deleted.contains(id)
+    }
+
+    /// The number of items in the index, including deleted ones.
+    pub fn num_items(&self) -> usize {
+        self.num_items
+    }
+
+    pub fn num_deleted(&self) -> usize {
+        self.deleted.len()
+    }
+
+    /// The fraction of the index's items that have been deleted, to help decide when it's worth
+    /// calling `compact`.
+    pub fn deleted_ratio(&self) -> f64 {
+        self.num_deleted() as f64 / self.num_items as f64
+    }
+
+    /// Build a new index containing only the items that haven't been deleted. Items keep their
+    /// original ids, and since dropping items doesn't disturb the Hilbert order of the rest, only
+    /// the parent nodes need to be recomputed, which is much cheaper than building from scratch.
+    ///
+    /// Panics if every item has been deleted.
+    pub fn compact(&self) -> FlatBush<T> {
+        let live: Vec<usize> = (0..self.num_items)
+            .filter(|i| !self.deleted.contains(self.indices.get(*i) as usize))
+            .collect();
+        assert!(!live.is_empty(), "can't compact an index whose items have all been deleted");
+
+        let num_items = live.len();
+        let (level_bounds, num_nodes) = level_bounds(num_items, self.node_size);
+
+        // node entries are box offsets, which need to fit along with the item ids
+        let mut indices = match self.indices {
+            IndexVec::U16(_) if num_nodes < 16384 => IndexVec::U16(vec![0; num_nodes]),
+            _ => IndexVec::U32(vec![0; num_nodes]),
+        };
+
+        let mut boxes = Vec::with_capacity(num_nodes * 4);
+        let (mut min_x, mut min_y, mut max_x, mut max_y) =
+            (T::max_value(), T::max_value(), T::min_value(), T::min_value());
+        for (i, item) in live.iter().enumerate() {
+            let pos = 4 * item;
+            let item_box = &self.boxes[pos..(pos + 4)];
+            if item_box[0] < min_x {
+                min_x = item_box[0];
+            }
+            if item_box[1] < min_y {
+                min_y = item_box[1];
+            }
+            if item_box[2] > max_x {
+                max_x = item_box[2];
+            }
+            if item_box[3] > max_y {
+                max_y = item_box[3];
+            }
+            boxes.extend_from_slice(item_box);
+            indices.set(i, self.indices.get(*item));
+        }
+
+        pack_levels(&mut boxes, &mut indices, &level_bounds, self.node_size);
+
+        let positions = self.positions.as_ref().map(|_| indices.invert(num_items));
+
+        FlatBush {
+            boxes,
+            indices,
+            level_bounds,
+            num_items,
+            node_size: self.node_size,
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+            deleted: BitSet::default(),
+            baseline_area: None,
+            positions,
+            compacted: true,
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::batch::batch;
+#[cfg(feature = "rayon")]
+use crate::batch::par_batch;
+use crate::dynamic::{LogStructured, StaticIndex, DEFAULT_BUFFER_SIZE};
+use crate::flatbush::{AllowedNumber, FlatBush, FlatBushBuilder, DEFAULT_NODE_SIZE};
+use crate::{BatchResults, QueryScratch};
+
+use core::ops::ControlFlow;
+
+impl<T: AllowedNumber> StaticIndex for FlatBush<T> {
+    type Item = [T; 4];
+
+    fn build<I: Iterator<Item = [T; 4]>>(boxes: I, node_size: usize) -> FlatBush<T> {
+        let mut builder = FlatBushBuilder::new_with_node_size(node_size);
+        builder.extend(boxes);
+        builder.finish()
+    }
+
+    fn for_each_item<F: FnMut(usize, [T; 4])>(&self, mut f: F) {
+        for i in 0..self.num_items {
+            let pos = 4 * i;
+            f(
+                self.indices.get(i) as usize,
+                [self.boxes[pos], self.boxes[pos + 1], self.boxes[pos + 2], self.boxes[pos + 3]],
+            );
+        }
+    }
+}
+
+/// A `FlatBush` that supports inserting and removing boxes, by keeping a small buffer of recent
+/// insertions plus a logarithmic series of static indexes that get merged as they fill up.
+#[derive(Clone)]
+pub struct DynamicFlatBush<T: AllowedNumber> {
+    inner: LogStructured<FlatBush<T>>,
+}
+
+impl<T: AllowedNumber> DynamicFlatBush<T> {
+    #[inline(always)]
+    pub fn new() -> DynamicFlatBush<T> {
+        DynamicFlatBush::new_with_node_size(DEFAULT_NODE_SIZE)
+    }
+
+    pub fn new_with_node_size(node_size: usize) -> DynamicFlatBush<T> {
+        DynamicFlatBush::new_with_buffer_size(node_size, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Like `new_with_node_size`, but also sets how many insertions are buffered before they get
+    /// indexed. Smaller buffers make queries cheaper and insertions more expensive.
+    pub fn new_with_buffer_size(node_size: usize, buffer_size: usize) -> DynamicFlatBush<T> {
+        DynamicFlatBush { inner: LogStructured::new(node_size, buffer_size) }
+    }
+
+    /// Add a box, returning its id. Ids are assigned sequentially and never reused.
+    pub fn insert<U: std::borrow::Borrow<[T; 4]>>(&mut self, new_box: U) -> usize {
+        self.inner.insert(*new_box.borrow())
+    }
+
+    /// Remove the box with the given id, returning whether it was present.
+    pub fn remove(&mut self, id: usize) -> bool {
+        self.inner.remove(id)
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.inner.contains(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn search_range<'a>(
+        &'a self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.inner.query(
+            move |b| overlaps(b, min_x, min_y, max_x, max_y),
+            move |bush| bush.search_range(min_x, min_y, max_x, max_y),
+        )
+    }
+
+    pub fn visit_range<B, F: FnMut(usize) -> ControlFlow<B>>(
+        &self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+        visitor: F,
+    ) -> ControlFlow<B> {
+        self.inner.visit(
+            |b| overlaps(b, min_x, min_y, max_x, max_y),
+            |bush, f| bush.visit_range(min_x, min_y, max_x, max_y, f),
+            visitor,
+        )
+    }
+
+    pub fn search_range_with<'s>(
+        &self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+        scratch: &'s mut QueryScratch,
+    ) -> &'s [usize] {
+        self.inner.visit_into(
+            scratch,
+            |b| overlaps(b, min_x, min_y, max_x, max_y),
+            |bush, f| bush.visit_range(min_x, min_y, max_x, max_y, f),
+        )
+    }
+
+    /// Run `search_range` for each `[min_x, min_y, max_x, max_y]` query.
+    pub fn search_range_batch(&self, queries: &[[T; 4]]) -> BatchResults {
+        batch(queries, |q, scratch| {
+            self.search_range_with(q[0], q[1], q[2], q[3], scratch);
+        })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn num_stored(&self) -> usize {
+        self.inner.num_stored()
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T: AllowedNumber + Send + Sync> DynamicFlatBush<T> {
+    /// Like `search_range_batch`, but spreads the queries across the rayon thread pool.
+    pub fn par_search_range_batch(&self, queries: &[[T; 4]]) -> BatchResults {
+        par_batch(queries, |q, scratch| {
+            self.search_range_with(q[0], q[1], q[2], q[3], scratch);
+        })
+    }
+}
+
+#[inline(always)]
+fn overlaps<T: AllowedNumber>(b: &[T; 4], min_x: T, min_y: T, max_x: T, max_y: T) -> bool {
+    max_x >= b[0] && max_y >= b[1] && min_x <= b[2] && min_y <= b[3]
+}
+
+impl<T: AllowedNumber> Default for DynamicFlatBush<T> {
+    fn default() -> Self {
+        DynamicFlatBush::new()
+    }
+}
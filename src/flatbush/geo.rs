@@ -0,0 +1,110 @@
+use std::collections::BinaryHeap;
+
+use crate::flatbush::queue::Candidate;
+use crate::flatbush::{FlatBush, FlatBushBuilder};
+use crate::geo::{box_dist, cos_lat, haver_sin_to_km, km_to_haver_sin};
+
+impl FlatBushBuilder<f64> {
+    /// Add a `[min_lon, min_lat, max_lon, max_lat]` box for use with the `geo_*` queries. A box
+    /// with `min_lon > max_lon` is taken to cross the antimeridian, and is stored with `max_lon`
+    /// shifted up by 360 so that its extent stays contiguous.
+    pub fn add_geo(&mut self, new_box: [f64; 4]) -> usize {
+        let [min_lon, min_lat, max_lon, max_lat] = new_box;
+        if min_lon > max_lon {
+            self.add([min_lon, min_lat, max_lon + 360., max_lat])
+        } else {
+            self.add(new_box)
+        }
+    }
+}
+
+impl FlatBush<f64> {
+    /// Like `search_range`, but for boxes stored as `[min_lon, min_lat, max_lon, max_lat]`: if
+    /// `min_lon > max_lon`, the range is taken to cross the antimeridian, and both sides of it are
+    /// searched. Items that cross the antimeridian themselves (added with `add_geo`) are found
+    /// from either side, and returned only once.
+    pub fn geo_search_range<'a>(
+        &'a self,
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+    ) -> impl Iterator<Item = usize> + 'a {
+        // items that cross the antimeridian extend past 180, so search the range both as given
+        // and shifted by a full turn, with whichever one is wrapped unwrapped past 180 as well
+        let (lo, hi) =
+            if min_lon > max_lon { (min_lon, max_lon + 360.) } else { (min_lon, max_lon) };
+        let (shifted_lo, shifted_hi) =
+            if min_lon > max_lon { (min_lon - 360., max_lon) } else { (lo + 360., hi + 360.) };
+
+        let first = self.search_range(lo, min_lat, hi, max_lat);
+        // anything that also overlaps the first range was already returned
+        let second = self
+            .search_range_boxes(shifted_lo, min_lat, shifted_hi, max_lat)
+            .filter(move |(_, b)| b[0] > hi || b[2] < lo)
+            .map(|(id, _)| id);
+
+        first.chain(second)
+    }
+
+    /// Find the (up to) `max_results` items closest to the given longitude and latitude (in
+    /// degrees), treating item boxes as `[min_lon, min_lat, max_lon, max_lat]` and measuring
+    /// great-circle distance, including across the antimeridian. Only items within
+    /// `max_distance` kilometres are returned; pass `f64::INFINITY` for no limit. Results are
+    /// `(id, distance in km)` pairs, closest first.
+    pub fn geo_around(
+        &self,
+        lon: f64,
+        lat: f64,
+        max_results: usize,
+        max_distance: f64,
+    ) -> Vec<(usize, f64)> {
+        let mut results = Vec::new();
+        if max_results == 0 {
+            return results;
+        }
+
+        let cos_lat = cos_lat(lat);
+        let max_haver_sin_dist = km_to_haver_sin(max_distance);
+        let dist = |pos: usize| {
+            let b = &self.boxes[pos..(pos + 4)];
+            box_dist(lon, lat, cos_lat, b[0], b[1], b[2], b[3])
+        };
+
+        let mut queue = BinaryHeap::new();
+        let root = self.boxes.len() - 4;
+        queue.push(Candidate { dist: dist(root), pos: root, id: None });
+
+        while let Some(candidate) = queue.pop() {
+            // everything left in the queue is at least this far away
+            if candidate.dist > max_haver_sin_dist {
+                break;
+            }
+
+            if let Some(id) = candidate.id {
+                results.push((id, haver_sin_to_km(candidate.dist)));
+                if results.len() == max_results {
+                    break;
+                }
+                continue;
+            }
+
+            let children = self.child_range(candidate.pos);
+            let is_leaf = children.start < self.num_items * 4;
+            for pos in children.step_by(4) {
+                let id = if is_leaf {
+                    let id = self.indices.get(pos >> 2) as usize;
+                    if self.deleted.contains(id) {
+                        continue;
+                    }
+                    Some(id)
+                } else {
+                    None
+                };
+                queue.push(Candidate { dist: dist(pos), pos, id });
+            }
+        }
+
+        results
+    }
+}
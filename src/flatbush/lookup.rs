@@ -0,0 +1,52 @@
+use crate::flatbush::{AllowedNumber, FlatBush};
+
+impl<T: AllowedNumber> FlatBush<T> {
+    /// The box of the item with the given id, or `None` if there's no such item or it's been
+    /// deleted. Takes constant time if the index was built with `set_id_lookup(true)`, and
+    /// linear time otherwise.
+    pub fn bbox(&self, id: usize) -> Option<[T; 4]> {
+        if self.deleted.contains(id) {
+            return None;
+        }
+
+        let pos = 4 * self.position_of(id)?;
+        Some([self.boxes[pos], self.boxes[pos + 1], self.boxes[pos + 2], self.boxes[pos + 3]])
+    }
+
+    /// All the items in the index that haven't been deleted, as `(id, [min_x, min_y, max_x,
+    /// max_y])` pairs in the order of the builder's space-filling curve.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, [T; 4])> + 'a {
+        (0..self.num_items)
+            .map(move |i| (self.indices.get(i) as usize, 4 * i))
+            .filter(move |(id, _)| !self.deleted.contains(*id))
+            .map(move |(id, pos)| {
+                (
+                    id,
+                    [
+                        self.boxes[pos],
+                        self.boxes[pos + 1],
+                        self.boxes[pos + 2],
+                        self.boxes[pos + 3],
+                    ],
+                )
+            })
+    }
+
+    // whether the index has an item (deleted or not) with the given id; freshly built indexes
+    // hold every id below their number of items, so only compacted ones need a search
+    pub(super) fn has_id(&self, id: usize) -> bool {
+        match &self.positions {
+            Some(positions) => positions.position(id).is_some(),
+            None if !self.compacted => id < self.num_items,
+            None => self.position_of(id).is_some(),
+        }
+    }
+
+    // the sorted position of the item with the given id
+    pub(super) fn position_of(&self, id: usize) -> Option<usize> {
+        match &self.positions {
+            Some(positions) => positions.position(id),
+            None => (0..self.num_items).find(|i| self.indices.get(*i) as usize == id),
+        }
+    }
+}
@@ -2,12 +2,38 @@ use num_traits::{AsPrimitive, Bounded, NumOps, Zero};
 
 use core::borrow::Borrow;
 use core::iter::FromIterator;
-
-use crate::util::IndexVec;
-
+use core::ops::{ControlFlow, Range};
+use std::sync::Arc;
+
+use crate::util::{BitSet, IndexVec};
+use crate::QueryScratch;
+
+mod aggregate;
+mod batch;
+mod containment;
+mod curve;
+mod delete;
+mod dynamic;
+mod geo;
+mod lookup;
+mod node;
+mod oriented;
+mod polygon;
+mod queue;
+mod ray;
+mod refit;
+mod segment;
 #[cfg(test)]
 mod test;
 
+pub use aggregate::{AggregateFlatBush, FnMonoid, Monoid};
+use curve::hilbert;
+pub use curve::{Hilbert, Hilbert32, Morton, SpaceFillingCurve};
+pub use dynamic::DynamicFlatBush;
+pub use node::Node;
+pub use polygon::{PolygonIndex, PolygonIndexBuilder};
+pub use segment::{SegmentIndex, SegmentIndexBuilder, Snap};
+
 pub trait AllowedNumber: PartialOrd + NumOps + AsPrimitive<f64> + Bounded + Zero
 where
     Self: std::marker::Sized,
@@ -27,12 +53,23 @@ pub struct FlatBush<T: AllowedNumber> {
     min_y: T,
     max_x: T,
     max_y: T,
+    // ids of deleted items
+    deleted: BitSet,
+    // `node_area` from before the first refit, if there's been one
+    baseline_area: Option<f64>,
+    // sorted position of each item id, if the builder was asked for one
+    positions: Option<IndexVec>,
+    // whether this came from `compact`, so that its ids might not run from 0 to `num_items - 1`
+    compacted: bool,
 }
 
 #[derive(Clone)]
 pub struct FlatBushBuilder<T: AllowedNumber> {
     boxes: Vec<T>,
     node_size: usize,
+    id_lookup: bool,
+    // `None` for the default `Hilbert` curve, which gets packed with narrower keys
+    curve: Option<Arc<dyn SpaceFillingCurve + Send + Sync>>,
     min_x: T,
     min_y: T,
     max_x: T,
@@ -49,6 +86,7 @@ impl<T: AllowedNumber> FlatBushBuilder<T> {
         FlatBushBuilder::new_with_node_size(DEFAULT_NODE_SIZE)
     }
 
+    #[allow(clippy::manual_clamp)]
     pub fn new_with_node_size(node_size: usize) -> FlatBushBuilder<T> {
         let node_size: usize = match node_size {
             x if x < MIN_NODE_SIZE => MIN_NODE_SIZE,
@@ -61,7 +99,16 @@ impl<T: AllowedNumber> FlatBushBuilder<T> {
         let max_x = T::min_value();
         let max_y = T::min_value();
 
-        FlatBushBuilder { boxes: Vec::new(), node_size, min_x, min_y, max_x, max_y }
+        FlatBushBuilder {
+            boxes: Vec::new(),
+            node_size,
+            id_lookup: false,
+            curve: None,
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
     }
 
     pub fn add<U: Borrow<[T; 4]>>(&mut self, new_box: U) -> usize {
@@ -85,22 +132,27 @@ impl<T: AllowedNumber> FlatBushBuilder<T> {
         (self.boxes.len() >> 2) - 1
     }
 
+    /// Whether to build a table mapping item ids to their position in the index, which makes
+    /// looking up or refitting items by id (`bbox`, `refit`) take constant rather than linear time.
+    /// Off by default.
+    pub fn set_id_lookup(&mut self, enabled: bool) {
+        self.id_lookup = enabled;
+    }
+
+    /// The curve used to order items before packing them into the tree. Items that are close
+    /// together along the curve end up in the same nodes, so this decides the shape of the tree.
+    /// `Hilbert` by default.
+    pub fn set_curve<C: SpaceFillingCurve + Send + Sync + 'static>(&mut self, curve: C) {
+        self.curve = Some(Arc::new(curve));
+    }
+
+    #[allow(clippy::needless_range_loop)]
     pub fn finish(mut self) -> FlatBush<T> {
         let num_items = self.boxes.len() >> 2;
 
         // calculate the total number of nodes in the R-tree to allocate space for
         // and the index of each tree level
-        let mut n = num_items;
-        let mut num_nodes = n;
-        let mut level_bounds = vec![n * 4];
-        loop {
-            n = ceiling_division(n, self.node_size);
-            num_nodes += n;
-            level_bounds.push(num_nodes * 4);
-            if n == 1 {
-                break;
-            }
-        }
+        let (level_bounds, num_nodes) = level_bounds(num_items, self.node_size);
 
         let mut indices = if num_nodes < 16384 {
             let mut v = vec![0; num_nodes];
@@ -122,6 +174,7 @@ impl<T: AllowedNumber> FlatBushBuilder<T> {
             self.boxes.push(self.min_y);
             self.boxes.push(self.max_x);
             self.boxes.push(self.max_y);
+            let positions = if self.id_lookup { Some(indices.invert(num_items)) } else { None };
             return FlatBush {
                 boxes: self.boxes,
                 indices,
@@ -132,6 +185,10 @@ impl<T: AllowedNumber> FlatBushBuilder<T> {
                 min_y: self.min_y,
                 max_x: self.max_x,
                 max_y: self.max_y,
+                deleted: BitSet::default(),
+                baseline_area: None,
+                positions,
+                compacted: false,
             };
         }
 
@@ -140,65 +197,55 @@ impl<T: AllowedNumber> FlatBushBuilder<T> {
         let width: f64 = if bush_max_x == bush_min_x { 1. } else { bush_max_x - bush_min_x };
         let height: f64 = if bush_max_y == bush_min_y { 1. } else { bush_max_y - bush_min_y };
 
-        let hilbert_max = ((1 << 16) - 1) as f64;
-        // map item centers into Hilbert coordinate space and calculate Hilbert values
-        let mut hilbert_values: Vec<_> = (0..num_items)
-            .map(|i| {
-                let pos = 4 * i;
-                let min_x: f64 = self.boxes[pos].as_();
-                let min_y: f64 = self.boxes[pos + 1].as_();
-                let max_x: f64 = self.boxes[pos + 2].as_();
-                let max_y: f64 = self.boxes[pos + 3].as_();
-                let x = (hilbert_max * ((min_x + max_x) / 2.0 - bush_min_x) / width).floor() as u32;
-                let y =
-                    (hilbert_max * ((min_y + max_y) / 2.0 - bush_min_y) / height).floor() as u32;
-                hilbert(x, y)
-            })
-            .collect();
-
-        // sort items by their Hilbert value (for packing later)
-        sort(
-            &mut hilbert_values,
-            self.boxes.as_mut_slice(),
-            &mut indices,
-            0,
-            num_items - 1,
-            self.node_size,
-        );
+        // offsets of item centers from the bottom-left of the index
+        let boxes = &self.boxes;
+        let centers = (0..num_items).map(|i| {
+            let pos = 4 * i;
+            let min_x: f64 = boxes[pos].as_();
+            let min_y: f64 = boxes[pos + 1].as_();
+            let max_x: f64 = boxes[pos + 2].as_();
+            let max_y: f64 = boxes[pos + 3].as_();
+            ((min_x + max_x) / 2.0 - bush_min_x, (min_y + max_y) / 2.0 - bush_min_y)
+        });
+
+        // calculate their positions along the curve, and sort items by them (for packing later)
+        match &self.curve {
+            None => {
+                let hilbert_max = ((1 << 16) - 1) as f64;
+                let mut hilbert_values: Vec<u32> = centers
+                    .map(|(x, y)| {
+                        let x = (hilbert_max * x / width).floor() as u32;
+                        let y = (hilbert_max * y / height).floor() as u32;
+                        hilbert(x, y)
+                    })
+                    .collect();
+                sort(
+                    &mut hilbert_values,
+                    self.boxes.as_mut_slice(),
+                    &mut indices,
+                    0,
+                    num_items - 1,
+                    self.node_size,
+                );
+            }
+            Some(curve) => {
+                let mut curve_values: Vec<u64> =
+                    centers.map(|(x, y)| curve.key(x, y, width, height)).collect();
+                sort(
+                    &mut curve_values,
+                    self.boxes.as_mut_slice(),
+                    &mut indices,
+                    0,
+                    num_items - 1,
+                    self.node_size,
+                );
+            }
+        }
 
         // generate nodes at each tree level, bottom-up
-        let mut pos = 0;
-        for i in 0..(level_bounds.len() - 1) {
-            let end = level_bounds[i];
-
-            // generate a parent node for each block of consecutive <node_size> nodes
-            while pos < end {
-                let node_index = pos;
-
-                // calculate bbox for the new node
-                let mut node_min_x: T = T::max_value();
-                let mut node_min_y: T = T::max_value();
-                let mut node_max_x: T = T::min_value();
-                let mut node_max_y: T = T::min_value();
-                for _i in 0..self.node_size {
-                    if pos >= end {
-                        break;
-                    }
-                    node_min_x = min(node_min_x, self.boxes[pos]);
-                    node_min_y = min(node_min_y, self.boxes[pos + 1]);
-                    node_max_x = max(node_max_x, self.boxes[pos + 2]);
-                    node_max_y = max(node_max_y, self.boxes[pos + 3]);
-                    pos += 4;
-                }
+        pack_levels(&mut self.boxes, &mut indices, &level_bounds, self.node_size);
 
-                // add the new node to the tree data
-                indices.set(self.boxes.len() >> 2, node_index as u32);
-                self.boxes.push(node_min_x);
-                self.boxes.push(node_min_y);
-                self.boxes.push(node_max_x);
-                self.boxes.push(node_max_y);
-            }
-        }
+        let positions = if self.id_lookup { Some(indices.invert(num_items)) } else { None };
 
         FlatBush {
             boxes: self.boxes,
@@ -210,11 +257,22 @@ impl<T: AllowedNumber> FlatBushBuilder<T> {
             min_y: self.min_y,
             max_x: self.max_x,
             max_y: self.max_y,
+            deleted: BitSet::default(),
+            baseline_area: None,
+            positions,
+            compacted: false,
         }
     }
 }
 
+impl<T: AllowedNumber> Default for FlatBushBuilder<T> {
+    fn default() -> Self {
+        FlatBushBuilder::new()
+    }
+}
+
 impl<T: AllowedNumber> FlatBush<T> {
+    #[allow(clippy::identity_op, clippy::filter_map_identity)]
     pub fn search_range<'a>(
         &'a self,
         min_x: T,
@@ -260,7 +318,12 @@ impl<T: AllowedNumber> FlatBush<T> {
 
             pos += 4;
             if node_index < self.num_items * 4 {
-                Some(Some(index))
+                // item; skip it if it's been deleted
+                if self.deleted.contains(index) {
+                    Some(None)
+                } else {
+                    Some(Some(index))
+                }
             } else {
                 queue.push(index); // node; add it to the search queue
                 Some(None)
@@ -269,6 +332,174 @@ impl<T: AllowedNumber> FlatBush<T> {
         .filter_map(|x| x)
     }
 
+    pub fn visit_range<B, F: FnMut(usize) -> ControlFlow<B>>(
+        &self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+        mut visitor: F,
+    ) -> ControlFlow<B> {
+        let intersects = |node_box: &[T]| {
+            max_x >= node_box[0]
+                && max_y >= node_box[1]
+                && min_x <= node_box[2]
+                && min_y <= node_box[3]
+        };
+        self.walk(self.boxes.len() - 4, &intersects, &mut visitor)
+    }
+
+    /// Like `search_range`, but yields each item's box along with its id.
+    pub fn search_range_boxes<'a>(
+        &'a self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+    ) -> impl Iterator<Item = (usize, [T; 4])> + 'a {
+        let intersects = move |node_box: &[T]| {
+            max_x >= node_box[0]
+                && max_y >= node_box[1]
+                && min_x <= node_box[2]
+                && min_y <= node_box[3]
+        };
+        self.search(intersects, intersects).map(move |(id, pos)| {
+            (id, [self.boxes[pos], self.boxes[pos + 1], self.boxes[pos + 2], self.boxes[pos + 3]])
+        })
+    }
+
+    // lazy, queue-based search that yields the id and box offset of each live item; parent nodes
+    // are descended into if `overlaps` returns true for their box, and items are yielded if
+    // `matches` returns true for theirs
+    fn search<'a, P, M>(
+        &'a self,
+        overlaps: P,
+        matches: M,
+    ) -> impl Iterator<Item = (usize, usize)> + 'a
+    where
+        P: Fn(&[T]) -> bool + 'a,
+        M: Fn(&[T]) -> bool + 'a,
+    {
+        let mut queue: Vec<usize> = vec![self.boxes.len() - 4];
+        let mut pos = 0;
+        let mut node_index = 0;
+        let mut end: usize = 0;
+
+        std::iter::from_fn(move || loop {
+            if pos >= end {
+                node_index = queue.pop()?;
+                // find the end index of the node
+                end = min(
+                    node_index + self.node_size * 4,
+                    upper_bound(node_index, &self.level_bounds),
+                );
+                pos = node_index;
+                continue;
+            }
+
+            let item_pos = pos;
+            pos += 4;
+            let item_box = &self.boxes[item_pos..(item_pos + 4)];
+
+            let index = self.indices.get(item_pos >> 2) as usize;
+            if node_index >= self.num_items * 4 {
+                if overlaps(item_box) {
+                    queue.push(index); // node; add it to the search queue
+                }
+            } else if matches(item_box) && !self.deleted.contains(index) {
+                return Some((index, item_pos));
+            }
+        })
+    }
+
+    pub fn search_range_with<'s>(
+        &self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+        scratch: &'s mut QueryScratch,
+    ) -> &'s [usize] {
+        let intersects = |node_box: &[T]| {
+            max_x >= node_box[0]
+                && max_y >= node_box[1]
+                && min_x <= node_box[2]
+                && min_y <= node_box[3]
+        };
+        self.search_into(scratch, &intersects)
+    }
+
+    // queue-based search that collects into the buffers of a reusable scratch instead of
+    // allocating; `overlaps` works the same as in `walk`
+    fn search_into<'s, P>(&self, scratch: &'s mut QueryScratch, overlaps: &P) -> &'s [usize]
+    where
+        P: Fn(&[T]) -> bool,
+    {
+        scratch.clear();
+        let QueryScratch { stack: queue, results } = scratch;
+        queue.push(self.boxes.len() - 4);
+
+        while let Some(node_index) = queue.pop() {
+            // find the end index of the node
+            let end =
+                min(node_index + self.node_size * 4, upper_bound(node_index, &self.level_bounds));
+
+            for pos in (node_index..end).step_by(4) {
+                if !overlaps(&self.boxes[pos..(pos + 4)]) {
+                    continue;
+                }
+
+                let index = self.indices.get(pos >> 2) as usize;
+                if node_index < self.num_items * 4 {
+                    if !self.deleted.contains(index) {
+                        results.push(index);
+                    }
+                } else {
+                    queue.push(index); // node; add it to the search queue
+                }
+            }
+        }
+
+        results
+    }
+
+    // the range of box offsets holding the children of the node at `pos`
+    fn child_range(&self, pos: usize) -> Range<usize> {
+        let start = self.indices.get(pos >> 2) as usize;
+        // find the end index of the node
+        let end = min(start + self.node_size * 4, upper_bound(start, &self.level_bounds));
+        start..end
+    }
+
+    // recursive, allocation-free counterpart to the queue-based searches: checks each child of the
+    // node at `node_index` against `overlaps`, visiting matching items and descending into matching
+    // nodes
+    fn walk<B, P, F>(&self, node_index: usize, overlaps: &P, visitor: &mut F) -> ControlFlow<B>
+    where
+        P: Fn(&[T]) -> bool,
+        F: FnMut(usize) -> ControlFlow<B>,
+    {
+        // find the end index of the node
+        let end = min(node_index + self.node_size * 4, upper_bound(node_index, &self.level_bounds));
+
+        for pos in (node_index..end).step_by(4) {
+            if !overlaps(&self.boxes[pos..(pos + 4)]) {
+                continue;
+            }
+
+            let index = self.indices.get(pos >> 2) as usize;
+            if node_index < self.num_items * 4 {
+                if !self.deleted.contains(index) {
+                    visitor(index)?;
+                }
+            } else {
+                self.walk(index, overlaps, visitor)?;
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+
     pub fn bounds(&self) -> [T; 4] {
         [self.min_x, self.min_y, self.max_x, self.max_y]
     }
@@ -290,6 +521,65 @@ impl<T: AllowedNumber, U: Borrow<[T; 4]>> FromIterator<U> for FlatBush<T> {
     }
 }
 
+// calculate the index of each tree level in the flattened box array, along with the total
+// number of nodes, including items
+fn level_bounds(num_items: usize, node_size: usize) -> (Vec<usize>, usize) {
+    let mut n = num_items;
+    let mut num_nodes = n;
+    let mut level_bounds = vec![n * 4];
+    loop {
+        n = ceiling_division(n, node_size);
+        num_nodes += n;
+        level_bounds.push(num_nodes * 4);
+        if n == 1 {
+            break;
+        }
+    }
+    (level_bounds, num_nodes)
+}
+
+// generate nodes at each tree level, bottom-up, given boxes that hold only the (sorted) items
+#[allow(clippy::needless_range_loop)]
+fn pack_levels<T: AllowedNumber>(
+    boxes: &mut Vec<T>,
+    indices: &mut IndexVec,
+    level_bounds: &[usize],
+    node_size: usize,
+) {
+    let mut pos = 0;
+    for i in 0..(level_bounds.len() - 1) {
+        let end = level_bounds[i];
+
+        // generate a parent node for each block of consecutive <node_size> nodes
+        while pos < end {
+            let node_index = pos;
+
+            // calculate bbox for the new node
+            let mut node_min_x: T = T::max_value();
+            let mut node_min_y: T = T::max_value();
+            let mut node_max_x: T = T::min_value();
+            let mut node_max_y: T = T::min_value();
+            for _i in 0..node_size {
+                if pos >= end {
+                    break;
+                }
+                node_min_x = min(node_min_x, boxes[pos]);
+                node_min_y = min(node_min_y, boxes[pos + 1]);
+                node_max_x = max(node_max_x, boxes[pos + 2]);
+                node_max_y = max(node_max_y, boxes[pos + 3]);
+                pos += 4;
+            }
+
+            // add the new node to the tree data
+            indices.set(boxes.len() >> 2, node_index as u32);
+            boxes.push(node_min_x);
+            boxes.push(node_min_y);
+            boxes.push(node_max_x);
+            boxes.push(node_max_y);
+        }
+    }
+}
+
 // binary search for the first value in the array bigger than the given
 fn upper_bound(value: usize, arr: &[usize]) -> usize {
     let mut i = 0;
@@ -305,9 +595,9 @@ fn upper_bound(value: usize, arr: &[usize]) -> usize {
     arr[i]
 }
 
-// custom quicksort that partially sorts bbox data alongside the hilbert values
-fn sort<T: AllowedNumber>(
-    values: &mut [u32],
+// custom quicksort that partially sorts bbox data alongside their space-filling curve values
+fn sort<T: AllowedNumber, V: PartialOrd + Copy>(
+    values: &mut [V],
     boxes: &mut [T],
     indices: &mut IndexVec,
     left: usize,
@@ -346,16 +636,14 @@ fn sort<T: AllowedNumber>(
 }
 
 // swap two values and two corresponding boxes
-fn swap<T: AllowedNumber>(
-    values: &mut [u32],
+fn swap<T: AllowedNumber, V>(
+    values: &mut [V],
     boxes: &mut [T],
     indices: &mut IndexVec,
     i: usize,
     j: usize,
 ) {
-    let temp = values[i];
-    values[i] = values[j];
-    values[j] = temp;
+    values.swap(i, j);
 
     let k = 4 * i;
     let m = 4 * j;
@@ -379,8 +667,9 @@ fn swap<T: AllowedNumber>(
 }
 
 #[inline(always)]
+#[allow(clippy::manual_div_ceil)]
 fn ceiling_division(a: usize, b: usize) -> usize {
-    return (a + b - 1) / b;
+    (a + b - 1) / b
 }
 
 // implementing these myself to make the library work with floats even though they're not
@@ -402,61 +691,3 @@ fn max<U: PartialOrd>(a: U, b: U) -> U {
         a
     }
 }
-
-// Fast Hilbert curve algorithm by http://threadlocalmutex.com/
-// Ported from C++ https://github.com/rawrunprotected/hilbert_curves (public domain)
-#[allow(non_snake_case)]
-fn hilbert(x: u32, y: u32) -> u32 {
-    let mut a = x ^ y;
-    let mut b = 0xFFFF ^ a;
-    let mut c = 0xFFFF ^ (x | y);
-    let mut d = x & (y ^ 0xFFFF);
-
-    let mut A = a | (b >> 1);
-    let mut B = (a >> 1) ^ a;
-    let mut C = ((c >> 1) ^ (b & (d >> 1))) ^ c;
-    let mut D = ((a & (c >> 1)) ^ (d >> 1)) ^ d;
-
-    a = A;
-    b = B;
-    c = C;
-    d = D;
-    A = (a & (a >> 2)) ^ (b & (b >> 2));
-    B = (a & (b >> 2)) ^ (b & ((a ^ b) >> 2));
-    C ^= (a & (c >> 2)) ^ (b & (d >> 2));
-    D ^= (b & (c >> 2)) ^ ((a ^ b) & (d >> 2));
-
-    a = A;
-    b = B;
-    c = C;
-    d = D;
-    A = (a & (a >> 4)) ^ (b & (b >> 4));
-    B = (a & (b >> 4)) ^ (b & ((a ^ b) >> 4));
-    C ^= (a & (c >> 4)) ^ (b & (d >> 4));
-    D ^= (b & (c >> 4)) ^ ((a ^ b) & (d >> 4));
-
-    a = A;
-    b = B;
-    c = C;
-    d = D;
-    C ^= (a & (c >> 8)) ^ (b & (d >> 8));
-    D ^= (b & (c >> 8)) ^ ((a ^ b) & (d >> 8));
-
-    a = C ^ (C >> 1);
-    b = D ^ (D >> 1);
-
-    let mut i0 = x ^ y;
-    let mut i1 = b | (0xFFFF ^ (i0 | a));
-
-    i0 = (i0 | (i0 << 8)) & 0x00FF00FF;
-    i0 = (i0 | (i0 << 4)) & 0x0F0F0F0F;
-    i0 = (i0 | (i0 << 2)) & 0x33333333;
-    i0 = (i0 | (i0 << 1)) & 0x55555555;
-
-    i1 = (i1 | (i1 << 8)) & 0x00FF00FF;
-    i1 = (i1 | (i1 << 4)) & 0x0F0F0F0F;
-    i1 = (i1 | (i1 << 2)) & 0x33333333;
-    i1 = (i1 | (i1 << 1)) & 0x55555555;
-
-    ((i1 << 1) | i0) >> 0
-}
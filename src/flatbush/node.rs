@@ -0,0 +1,71 @@
+use crate::flatbush::{AllowedNumber, FlatBush};
+
+/// A read-only handle to a single node of a `FlatBush`'s packed R-tree.
+///
+/// Level 0 holds the indexed items themselves; each level above it holds parent nodes of up to
+/// `node_size` children, and the single node at the top level is the root.
+#[derive(Clone, Copy)]
+pub struct Node<'a, T: AllowedNumber> {
+    bush: &'a FlatBush<T>,
+    pos: usize,
+    level: usize,
+}
+
+impl<'a, T: AllowedNumber> Node<'a, T> {
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn bbox(&self) -> [T; 4] {
+        let boxes = &self.bush.boxes;
+        [boxes[self.pos], boxes[self.pos + 1], boxes[self.pos + 2], boxes[self.pos + 3]]
+    }
+
+    pub fn is_item(&self) -> bool {
+        self.level == 0
+    }
+
+    /// The id returned by `FlatBushBuilder::add` for this item, or `None` for internal nodes.
+    pub fn id(&self) -> Option<usize> {
+        if self.is_item() {
+            Some(self.bush.indices.get(self.pos >> 2) as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = Node<'a, T>> + 'a {
+        let bush = self.bush;
+        let children = if self.is_item() { 0..0 } else { bush.child_range(self.pos) };
+        let level = self.level.saturating_sub(1);
+
+        children.step_by(4).map(move |pos| Node { bush, pos, level })
+    }
+}
+
+impl<T: AllowedNumber> FlatBush<T> {
+    pub fn root(&self) -> Node<'_, T> {
+        Node { bush: self, pos: self.boxes.len() - 4, level: self.num_levels() - 1 }
+    }
+
+    /// Number of tree levels, including the item level and the root.
+    pub fn num_levels(&self) -> usize {
+        self.level_bounds.len()
+    }
+
+    pub fn node_size(&self) -> usize {
+        self.node_size
+    }
+
+    /// Walk the tree depth-first from the root, calling `visitor` on each node. The children of a
+    /// node are only visited if `visitor` returns `true` for it.
+    pub fn visit_nodes<F: FnMut(&Node<'_, T>) -> bool>(&self, mut visitor: F) {
+        let mut stack = vec![self.root()];
+
+        while let Some(node) = stack.pop() {
+            if visitor(&node) {
+                stack.extend(node.children());
+            }
+        }
+    }
+}
@@ -0,0 +1,11 @@
+use crate::flatbush::{AllowedNumber, FlatBush};
+use crate::geometry::OrientedRect;
+
+impl<T: AllowedNumber> FlatBush<T> {
+    /// Search for items whose boxes intersect a rotated rectangle. Nodes are pruned with a
+    /// separating axis test against the rectangle itself, rather than its bounding box.
+    pub fn search_oriented<'a>(&'a self, rect: OrientedRect) -> impl Iterator<Item = usize> + 'a {
+        let intersects = move |node_box: &[T]| rect.intersects_box(node_box);
+        self.search(intersects, intersects).map(|(id, _)| id)
+    }
+}
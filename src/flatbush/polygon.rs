@@ -0,0 +1,269 @@
+use core::ops::Range;
+
+use crate::flatbush::{max, min, AllowedNumber, FlatBush, FlatBushBuilder, DEFAULT_NODE_SIZE};
+use crate::geometry::slab_entry;
+
+/// A collection of polygons, indexed by their bounding boxes in a `FlatBush`, with queries that
+/// check candidates against the actual polygon geometry so that only true hits are returned.
+///
+/// Each polygon is a list of rings: the first is its outline, and any others are holes in it.
+/// Rings can be given either open or closed (with the first point repeated at the end).
+#[derive(Clone)]
+pub struct PolygonIndex<T: AllowedNumber> {
+    bush: FlatBush<T>,
+    // the rings of every polygon, in the same order as the items in `bush`; the polygon at sorted
+    // position i has rings polygon_offsets[i]..polygon_offsets[i + 1], and ring j has points
+    // ring_offsets[j]..ring_offsets[j + 1]
+    points: Vec<[T; 2]>,
+    ring_offsets: Vec<usize>,
+    polygon_offsets: Vec<usize>,
+}
+
+#[derive(Clone)]
+pub struct PolygonIndexBuilder<T: AllowedNumber> {
+    builder: FlatBushBuilder<T>,
+    // same layout as in `PolygonIndex`, but in the order polygons were added
+    points: Vec<[T; 2]>,
+    ring_offsets: Vec<usize>,
+    polygon_offsets: Vec<usize>,
+}
+
+impl<T: AllowedNumber> PolygonIndexBuilder<T> {
+    #[inline(always)]
+    pub fn new() -> PolygonIndexBuilder<T> {
+        PolygonIndexBuilder::new_with_node_size(DEFAULT_NODE_SIZE)
+    }
+
+    pub fn new_with_node_size(node_size: usize) -> PolygonIndexBuilder<T> {
+        PolygonIndexBuilder {
+            builder: FlatBushBuilder::new_with_node_size(node_size),
+            points: Vec::new(),
+            ring_offsets: vec![0],
+            polygon_offsets: vec![0],
+        }
+    }
+
+    /// Add a polygon, given as its outline followed by any holes, and return its id.
+    pub fn add<R: AsRef<[[T; 2]]>>(&mut self, rings: &[R]) -> usize {
+        let outline = rings.first().map(AsRef::as_ref).unwrap_or(&[]);
+        assert!(!outline.is_empty(), "polygon has no outline");
+
+        let mut bbox = [outline[0][0], outline[0][1], outline[0][0], outline[0][1]];
+        for p in outline {
+            bbox = [min(bbox[0], p[0]), min(bbox[1], p[1]), max(bbox[2], p[0]), max(bbox[3], p[1])];
+        }
+
+        for ring in rings {
+            self.points.extend_from_slice(ring.as_ref());
+            self.ring_offsets.push(self.points.len());
+        }
+        self.polygon_offsets.push(self.ring_offsets.len() - 1);
+
+        self.builder.add(bbox)
+    }
+
+    pub fn finish(self) -> PolygonIndex<T> {
+        let bush = self.builder.finish();
+
+        // copy the rings over in index order, so that polygons that are near each other in the
+        // tree are near each other in memory
+        let mut points = Vec::with_capacity(self.points.len());
+        let mut ring_offsets = vec![0];
+        let mut polygon_offsets = vec![0];
+        for i in 0..bush.num_items {
+            let id = bush.indices.get(i) as usize;
+            for ring in self.polygon_offsets[id]..self.polygon_offsets[id + 1] {
+                let range = self.ring_offsets[ring]..self.ring_offsets[ring + 1];
+                points.extend_from_slice(&self.points[range]);
+                ring_offsets.push(points.len());
+            }
+            polygon_offsets.push(ring_offsets.len() - 1);
+        }
+
+        PolygonIndex { bush, points, ring_offsets, polygon_offsets }
+    }
+}
+
+impl<T: AllowedNumber> Default for PolygonIndexBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: AllowedNumber> PolygonIndex<T> {
+    /// The underlying index of polygon bounding boxes.
+    pub fn bush(&self) -> &FlatBush<T> {
+        &self.bush
+    }
+
+    /// Number of polygons in the index.
+    pub fn len(&self) -> usize {
+        self.bush.num_items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The rings of the polygon with the given id, outline first, or `None` if there's no such
+    /// polygon. Takes linear time.
+    pub fn rings(&self, id: usize) -> Option<impl Iterator<Item = &[[T; 2]]> + '_> {
+        Some(self.rings_at(self.bush.position_of(id)?))
+    }
+
+    /// Search for polygons that contain the point `(x, y)`. Points inside a hole aren't contained.
+    pub fn search_point<'a>(&'a self, x: T, y: T) -> impl Iterator<Item = usize> + 'a {
+        let (px, py) = (x.as_(), y.as_());
+        self.bush
+            .search_range_positions(x, y, x, y)
+            .filter(move |&(_, i)| polygon_contains(self.rings_at(i), px, py))
+            .map(|(id, _)| id)
+    }
+
+    /// Search for polygons that intersect the query box, including ones that lie inside it or
+    /// that it lies inside.
+    pub fn search_range<'a>(
+        &'a self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let query = [min_x, min_y, max_x, max_y];
+        self.bush
+            .search_range_positions(min_x, min_y, max_x, max_y)
+            .filter(move |&(_, i)| {
+                // either an edge touches the box, or the box is entirely inside or outside the
+                // polygon, in which case any one of its corners tells which
+                self.rings_at(i).any(|ring| ring_crosses_box(ring, &query))
+                    || polygon_contains(self.rings_at(i), min_x.as_(), min_y.as_())
+            })
+            .map(|(id, _)| id)
+    }
+
+    fn rings_at(&self, i: usize) -> impl Iterator<Item = &[[T; 2]]> + '_ {
+        let rings: Range<usize> = self.polygon_offsets[i]..self.polygon_offsets[i + 1];
+        rings.map(move |ring| &self.points[self.ring_offsets[ring]..self.ring_offsets[ring + 1]])
+    }
+}
+
+impl<T: AllowedNumber> FlatBush<T> {
+    // like `search_range`, but yields each item's sorted position along with its id
+    fn search_range_positions<'a>(
+        &'a self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let intersects = move |node_box: &[T]| {
+            max_x >= node_box[0]
+                && max_y >= node_box[1]
+                && min_x <= node_box[2]
+                && min_y <= node_box[3]
+        };
+        self.search(intersects, intersects).map(|(id, pos)| (id, pos >> 2))
+    }
+}
+
+impl<T: AllowedNumber> FlatBush<T> {
+    /// Search for items whose boxes intersect the polygon with the given outline, which can be
+    /// open or closed. Nodes that lie entirely inside the polygon have all their items returned
+    /// without testing them individually.
+    pub fn search_polygon<'a>(&'a self, polygon: &'a [[T; 2]]) -> impl Iterator<Item = usize> + 'a {
+        let classify = move |pos: usize| {
+            let b = &self.boxes[pos..(pos + 4)];
+            if ring_crosses_box(polygon, b) {
+                Relation::Crossing
+            } else if ring_contains(polygon, b[0].as_(), b[1].as_()) {
+                // no edge touches the box, so it's entirely on one side
+                Relation::Inside
+            } else {
+                Relation::Outside
+            }
+        };
+
+        // box positions still to visit, and whether they're known to be inside the polygon
+        let mut stack: Vec<(usize, bool)> = Vec::new();
+        let root = self.boxes.len() - 4;
+        match classify(root) {
+            Relation::Outside => {}
+            relation => stack.push((root, relation == Relation::Inside)),
+        }
+
+        std::iter::from_fn(move || {
+            while let Some((pos, inside)) = stack.pop() {
+                if pos < self.num_items * 4 {
+                    let id = self.indices.get(pos >> 2) as usize;
+                    if !self.deleted.contains(id) {
+                        return Some(id);
+                    }
+                    continue;
+                }
+
+                for child in self.child_range(pos).step_by(4) {
+                    if inside {
+                        stack.push((child, true));
+                        continue;
+                    }
+                    match classify(child) {
+                        Relation::Outside => {}
+                        relation => stack.push((child, relation == Relation::Inside)),
+                    }
+                }
+            }
+            None
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Relation {
+    Outside,
+    Inside,
+    Crossing,
+}
+
+// even-odd test of a point against all of a polygon's rings, so points in holes are outside
+pub(super) fn polygon_contains<'a, T: AllowedNumber, I>(rings: I, x: f64, y: f64) -> bool
+where
+    I: Iterator<Item = &'a [[T; 2]]>,
+{
+    rings.fold(false, |inside, ring| inside != ring_contains(ring, x, y))
+}
+
+pub(super) fn ring_contains<T: AllowedNumber>(ring: &[[T; 2]], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    let mut prev = match ring.last() {
+        Some(p) => [p[0].as_(), p[1].as_()],
+        None => return false,
+    };
+
+    for p in ring {
+        let curr: [f64; 2] = [p[0].as_(), p[1].as_()];
+        if (curr[1] > y) != (prev[1] > y)
+            && x < (prev[0] - curr[0]) * (y - curr[1]) / (prev[1] - curr[1]) + curr[0]
+        {
+            inside = !inside;
+        }
+        prev = curr;
+    }
+
+    inside
+}
+
+// whether any edge of the ring touches the box
+pub(super) fn ring_crosses_box<T: AllowedNumber>(ring: &[[T; 2]], b: &[T]) -> bool {
+    let mut prev = match ring.last() {
+        Some(p) => [p[0].as_(), p[1].as_()],
+        None => return false,
+    };
+
+    ring.iter().any(|p| {
+        let curr: [f64; 2] = [p[0].as_(), p[1].as_()];
+        let dir = [curr[0] - prev[0], curr[1] - prev[1]];
+        let hit = slab_entry(prev, dir, 1., b).is_some();
+        prev = curr;
+        hit
+    })
+}
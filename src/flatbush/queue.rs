@@ -0,0 +1,30 @@
+use core::cmp::Ordering;
+
+// an entry in a best-first search, keyed by some lower bound on the distance to anything in it;
+// `id` is set for items and `None` for parent nodes
+pub(super) struct Candidate {
+    pub dist: f64,
+    pub pos: usize,
+    pub id: Option<usize>,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, so that the max-heap pops the closest candidate first
+        other.dist.partial_cmp(&self.dist).unwrap_or(Ordering::Equal)
+    }
+}
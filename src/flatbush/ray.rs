@@ -0,0 +1,68 @@
+use std::collections::BinaryHeap;
+
+use crate::flatbush::queue::Candidate;
+use crate::flatbush::{AllowedNumber, FlatBush};
+use crate::geometry::slab_entry;
+
+impl<T: AllowedNumber> FlatBush<T> {
+    /// Search for items whose boxes are crossed by the segment from `(x0, y0)` to `(x1, y1)`, in
+    /// the order the segment enters them.
+    pub fn search_segment<'a>(
+        &'a self,
+        x0: T,
+        y0: T,
+        x1: T,
+        y1: T,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let dir = [x1.as_() - x0.as_(), y1.as_() - y0.as_()];
+        self.raycast([x0, y0], dir, 1.).map(|(id, _)| id)
+    }
+
+    /// Search for items whose boxes are hit by the ray from `origin` along `dir`, as far as
+    /// `origin + max_t * dir`. Yields `(id, t)` pairs, where `t` is where the ray enters the box
+    /// (0 if it starts inside it), front to back, and only searches as much of the tree as it
+    /// needs to, so stopping at the first hit is cheap.
+    pub fn raycast<'a>(
+        &'a self,
+        origin: [T; 2],
+        dir: [f64; 2],
+        max_t: f64,
+    ) -> impl Iterator<Item = (usize, f64)> + 'a {
+        let origin = [origin[0].as_(), origin[1].as_()];
+        let entry = move |pos: usize| slab_entry(origin, dir, max_t, &self.boxes[pos..(pos + 4)]);
+
+        let mut queue = BinaryHeap::new();
+        let root = self.boxes.len() - 4;
+        if let Some(t) = entry(root) {
+            queue.push(Candidate { dist: t, pos: root, id: None });
+        }
+
+        std::iter::from_fn(move || {
+            while let Some(candidate) = queue.pop() {
+                if let Some(id) = candidate.id {
+                    return Some((id, candidate.dist));
+                }
+
+                let children = self.child_range(candidate.pos);
+                let is_leaf = children.start < self.num_items * 4;
+                for pos in children.step_by(4) {
+                    let t = match entry(pos) {
+                        Some(t) => t,
+                        None => continue,
+                    };
+                    let id = if is_leaf {
+                        let id = self.indices.get(pos >> 2) as usize;
+                        if self.deleted.contains(id) {
+                            continue;
+                        }
+                        Some(id)
+                    } else {
+                        None
+                    };
+                    queue.push(Candidate { dist: t, pos, id });
+                }
+            }
+            None
+        })
+    }
+}
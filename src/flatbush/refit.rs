@@ -0,0 +1,104 @@
+use crate::flatbush::{max, min, AllowedNumber, FlatBush};
+
+impl<T: AllowedNumber> FlatBush<T> {
+    /// Replace the box of the item with the given id, and grow or shrink its ancestors to fit.
+    /// The item keeps its place in the tree, so if items drift far from where they were when the
+    /// index was built, queries will slow down; see `refit_overhead`. Returns whether the item
+    /// was found; ids that aren't in the index (including ones left out by an earlier `compact`)
+    /// leave it untouched.
+    pub fn refit(&mut self, id: usize, new_box: [T; 4]) -> bool {
+        let i = match self.position_of(id) {
+            Some(i) => i,
+            None => return false,
+        };
+        self.capture_baseline_area();
+
+        let mut pos = 4 * i;
+        self.boxes[pos..(pos + 4)].copy_from_slice(&new_box);
+
+        // walk up the levels, recomputing each ancestor from its children
+        for level in 0..(self.level_bounds.len() - 1) {
+            let level_start = if level == 0 { 0 } else { self.level_bounds[level - 1] };
+            let parent = self.level_bounds[level] + (pos - level_start) / (4 * self.node_size) * 4;
+            self.refit_node(parent);
+            pos = parent;
+        }
+
+        self.update_bounds();
+        true
+    }
+
+    /// Replace the box of every item with the one returned by `new_box` for its id, then
+    /// recompute every node. Cheaper than calling `refit` for each item when most of them move.
+    pub fn refit_all<F: FnMut(usize) -> [T; 4]>(&mut self, mut new_box: F) {
+        self.capture_baseline_area();
+
+        for i in 0..self.num_items {
+            let pos = 4 * i;
+            let item_box = new_box(self.indices.get(i) as usize);
+            self.boxes[pos..(pos + 4)].copy_from_slice(&item_box);
+        }
+
+        // nodes are stored bottom-up, so each node's children are always refit before it
+        for pos in (self.level_bounds[0]..self.boxes.len()).step_by(4) {
+            self.refit_node(pos);
+        }
+
+        self.update_bounds();
+    }
+
+    /// The total area of the tree's parent nodes. This is proportional to the expected number of
+    /// nodes a small query has to visit, so it's a rough measure of query cost.
+    pub fn node_area(&self) -> f64 {
+        let mut area = 0.;
+        for pos in (self.level_bounds[0]..self.boxes.len()).step_by(4) {
+            let width: f64 = self.boxes[pos + 2].as_() - self.boxes[pos].as_();
+            let height: f64 = self.boxes[pos + 3].as_() - self.boxes[pos + 1].as_();
+            area += width * height;
+        }
+        area
+    }
+
+    /// How much `node_area` has grown as a result of refitting, as a ratio of its current value
+    /// to its value when the index was built; 1.0 if nothing has been refit. Once this climbs
+    /// well above 1, rebuilding the index from scratch will likely pay for itself.
+    pub fn refit_overhead(&self) -> f64 {
+        match self.baseline_area {
+            Some(baseline) if baseline > 0. => self.node_area() / baseline,
+            _ => 1.,
+        }
+    }
+
+    fn capture_baseline_area(&mut self) {
+        if self.baseline_area.is_none() {
+            self.baseline_area = Some(self.node_area());
+        }
+    }
+
+    // recompute the box of the node at `pos` from its children
+    fn refit_node(&mut self, pos: usize) {
+        let mut node_min_x: T = T::max_value();
+        let mut node_min_y: T = T::max_value();
+        let mut node_max_x: T = T::min_value();
+        let mut node_max_y: T = T::min_value();
+        for child in self.child_range(pos).step_by(4) {
+            node_min_x = min(node_min_x, self.boxes[child]);
+            node_min_y = min(node_min_y, self.boxes[child + 1]);
+            node_max_x = max(node_max_x, self.boxes[child + 2]);
+            node_max_y = max(node_max_y, self.boxes[child + 3]);
+        }
+
+        self.boxes[pos] = node_min_x;
+        self.boxes[pos + 1] = node_min_y;
+        self.boxes[pos + 2] = node_max_x;
+        self.boxes[pos + 3] = node_max_y;
+    }
+
+    fn update_bounds(&mut self) {
+        let root = self.boxes.len() - 4;
+        self.min_x = self.boxes[root];
+        self.min_y = self.boxes[root + 1];
+        self.max_x = self.boxes[root + 2];
+        self.max_y = self.boxes[root + 3];
+    }
+}
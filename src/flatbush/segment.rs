@@ -0,0 +1,139 @@
+use std::collections::BinaryHeap;
+
+use crate::flatbush::queue::Candidate;
+use crate::flatbush::{max, min, AllowedNumber, FlatBush, FlatBushBuilder, DEFAULT_NODE_SIZE};
+use crate::geometry::{box_sq_dist, project, sq_dist};
+
+/// A collection of line segments, indexed by their bounding boxes in a `FlatBush`, for finding
+/// the segments nearest to a point, e.g. to snap GPS fixes onto a road network.
+#[derive(Clone)]
+pub struct SegmentIndex<T: AllowedNumber> {
+    bush: FlatBush<T>,
+    // `[x0, y0, x1, y1]` of every segment, in the same order as the items in `bush`
+    segments: Vec<[T; 4]>,
+}
+
+#[derive(Clone)]
+pub struct SegmentIndexBuilder<T: AllowedNumber> {
+    builder: FlatBushBuilder<T>,
+    // in the order segments were added
+    segments: Vec<[T; 4]>,
+}
+
+/// The closest point on a segment to a query point, as returned by `SegmentIndex::nearest`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Snap {
+    pub id: usize,
+    pub distance: f64,
+    pub point: [f64; 2],
+    /// How far along the segment `point` is, from 0 at its start to 1 at its end.
+    pub fraction: f64,
+}
+
+impl<T: AllowedNumber> SegmentIndexBuilder<T> {
+    #[inline(always)]
+    pub fn new() -> SegmentIndexBuilder<T> {
+        SegmentIndexBuilder::new_with_node_size(DEFAULT_NODE_SIZE)
+    }
+
+    pub fn new_with_node_size(node_size: usize) -> SegmentIndexBuilder<T> {
+        SegmentIndexBuilder {
+            builder: FlatBushBuilder::new_with_node_size(node_size),
+            segments: Vec::new(),
+        }
+    }
+
+    /// Add the segment from `start` to `end`, and return its id.
+    pub fn add(&mut self, start: [T; 2], end: [T; 2]) -> usize {
+        self.segments.push([start[0], start[1], end[0], end[1]]);
+        self.builder.add([
+            min(start[0], end[0]),
+            min(start[1], end[1]),
+            max(start[0], end[0]),
+            max(start[1], end[1]),
+        ])
+    }
+
+    pub fn finish(self) -> SegmentIndex<T> {
+        let added = self.segments;
+        let bush = self.builder.finish();
+        let segments = (0..bush.num_items).map(|i| added[bush.indices.get(i) as usize]).collect();
+
+        SegmentIndex { bush, segments }
+    }
+}
+
+impl<T: AllowedNumber> Default for SegmentIndexBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: AllowedNumber> SegmentIndex<T> {
+    /// The underlying index of segment bounding boxes.
+    pub fn bush(&self) -> &FlatBush<T> {
+        &self.bush
+    }
+
+    /// Number of segments in the index.
+    pub fn len(&self) -> usize {
+        self.bush.num_items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Find the (up to) `max_results` segments closest to `(x, y)`, within `max_distance` of it;
+    /// pass `f64::INFINITY` for no limit. Results are closest first.
+    pub fn nearest(&self, x: T, y: T, max_results: usize, max_distance: f64) -> Vec<Snap> {
+        let mut results = Vec::new();
+        if max_results == 0 || self.is_empty() {
+            return results;
+        }
+
+        let p = [x.as_(), y.as_()];
+        let max_sq_dist = max_distance * max_distance;
+        let bush = &self.bush;
+
+        let mut queue = BinaryHeap::new();
+        let root = bush.boxes.len() - 4;
+        queue.push(Candidate { dist: box_sq_dist(p, &bush.boxes[root..]), pos: root, id: None });
+
+        while let Some(candidate) = queue.pop() {
+            // everything left in the queue is at least this far away
+            if candidate.dist > max_sq_dist {
+                break;
+            }
+
+            if let Some(id) = candidate.id {
+                let (point, fraction) = project(p, self.segments[candidate.pos >> 2]);
+                results.push(Snap { id, distance: candidate.dist.sqrt(), point, fraction });
+                if results.len() == max_results {
+                    break;
+                }
+                continue;
+            }
+
+            let children = bush.child_range(candidate.pos);
+            let is_leaf = children.start < bush.num_items * 4;
+            for pos in children.step_by(4) {
+                if is_leaf {
+                    let id = bush.indices.get(pos >> 2) as usize;
+                    if bush.deleted.contains(id) {
+                        continue;
+                    }
+                    // items are queued by their exact distance, so they come out in order
+                    let (point, _) = project(p, self.segments[pos >> 2]);
+                    let dist = sq_dist(p, point);
+                    queue.push(Candidate { dist, pos, id: Some(id) });
+                } else {
+                    let dist = box_sq_dist(p, &bush.boxes[pos..(pos + 4)]);
+                    queue.push(Candidate { dist, pos, id: None });
+                }
+            }
+        }
+
+        results
+    }
+}
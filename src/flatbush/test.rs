@@ -1,8 +1,10 @@
 use once_cell::sync::Lazy;
 
+use core::ops::ControlFlow;
 use std::convert::TryInto;
 
 use crate::flatbush::*;
+use crate::{OrientedRect, QueryScratch};
 
 static DATA: Lazy<Vec<u32>> = Lazy::new(|| {
     vec![
@@ -27,6 +29,7 @@ static DATA: Lazy<Vec<u32>> = Lazy::new(|| {
     ]
 });
 
+#[allow(clippy::needless_return)]
 fn create_index() -> FlatBush<u32> {
     let mut builder = FlatBushBuilder::new();
 
@@ -38,6 +41,7 @@ fn create_index() -> FlatBush<u32> {
     return index;
 }
 
+#[allow(clippy::needless_return)]
 fn create_small_index(num_items: usize, node_size: usize) -> FlatBush<u32> {
     let mut builder = FlatBushBuilder::new_with_node_size(node_size);
     for i in (0..(4 * num_items)).step_by(4) {
@@ -97,6 +101,7 @@ fn skips_sorting_less_than_node_size_number_of_rectangles() {
 }
 
 #[test]
+#[allow(clippy::unit_cmp)]
 fn performs_bbox_search() {
     // performs bbox search
     let index = create_index();
@@ -166,3 +171,917 @@ fn returns_index_of_newly_added_rectangle() {
     let expected_sequence: Vec<usize> = (0..5).collect();
     assert_eq!(ids, expected_sequence);
 }
+
+#[test]
+fn visits_every_node() {
+    let index = create_index();
+
+    let mut item_ids = Vec::new();
+    let mut num_internal = 0;
+    index.visit_nodes(|node| {
+        let bbox = node.bbox();
+        for child in node.children() {
+            assert_eq!(child.level() + 1, node.level(), "children are one level down");
+
+            let child_bbox = child.bbox();
+            assert!(
+                child_bbox[0] >= bbox[0]
+                    && child_bbox[1] >= bbox[1]
+                    && child_bbox[2] <= bbox[2]
+                    && child_bbox[3] <= bbox[3],
+                "child box is contained in its parent"
+            );
+        }
+
+        match node.id() {
+            Some(id) => {
+                assert!(node.is_item());
+                assert_eq!(
+                    node.bbox(),
+                    [DATA[4 * id], DATA[4 * id + 1], DATA[4 * id + 2], DATA[4 * id + 3]]
+                );
+                item_ids.push(id);
+            }
+            None => num_internal += 1,
+        }
+        true
+    });
+
+    item_ids.sort();
+    assert_eq!(item_ids, (0..(DATA.len() / 4)).collect::<Vec<_>>());
+    assert_eq!(num_internal, index.indices.len() - DATA.len() / 4);
+    assert_eq!(index.root().bbox(), index.bounds());
+    assert_eq!(index.root().level(), index.num_levels() - 1);
+
+    // pruning at the root skips everything else
+    let mut visited = 0;
+    index.visit_nodes(|_| {
+        visited += 1;
+        false
+    });
+    assert_eq!(visited, 1);
+}
+
+#[test]
+fn performs_bbox_visit() {
+    let index = create_index();
+
+    let mut expected: Vec<_> = index.search_range(40, 40, 60, 60).collect();
+    let mut results = Vec::new();
+    let flow = index.visit_range(40, 40, 60, 60, |id| {
+        results.push(id);
+        ControlFlow::<()>::Continue(())
+    });
+    assert_eq!(flow, ControlFlow::Continue(()));
+
+    expected.sort();
+    results.sort();
+    assert_eq!(results, expected);
+
+    let mut count = 0;
+    let flow = index.visit_range(0, 0, 100, 100, |id| {
+        count += 1;
+        if count == 3 {
+            ControlFlow::Break(id)
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert!(flow.is_break());
+    assert_eq!(count, 3);
+}
+
+#[test]
+fn performs_bbox_search_with_scratch() {
+    let index = create_index();
+    let mut scratch = QueryScratch::new();
+
+    for &(min, max) in &[(40, 60), (0, 100), (10, 20)] {
+        let mut expected: Vec<_> = index.search_range(min, min, max, max).collect();
+        let mut results = index.search_range_with(min, min, max, max, &mut scratch).to_vec();
+
+        expected.sort();
+        results.sort();
+        assert_eq!(results, expected);
+    }
+}
+
+#[test]
+fn performs_batch_bbox_search() {
+    let index = create_index();
+    let queries: Vec<[u32; 4]> = (0..50).map(|i| [i, i, i + 20, i + 30]).collect();
+
+    let results = index.search_range_batch(&queries);
+    assert_eq!(results.len(), queries.len());
+    for (q, hits) in queries.iter().zip(results.iter()) {
+        assert_eq!(hits, index.search_range_with(q[0], q[1], q[2], q[3], &mut QueryScratch::new()));
+    }
+
+    #[cfg(feature = "rayon")]
+    assert_eq!(index.par_search_range_batch(&queries), results);
+}
+
+#[test]
+fn aggregates_intersecting_items() {
+    let mut builder = FlatBushBuilder::new();
+    for i in (0..DATA.len()).step_by(4) {
+        builder.add([DATA[i], DATA[i + 1], DATA[i + 2], DATA[i + 3]]);
+    }
+    // weight each item by its id, and track both a sum and a max
+    let values: Vec<(u64, usize)> = (0..(DATA.len() / 4)).map(|i| (i as u64, i)).collect();
+    let monoid =
+        FnMonoid::new((0, 0), |a: &(u64, usize), b: &(u64, usize)| (a.0 + b.0, a.1.max(b.1)));
+    let index = builder.finish_with_aggregate(monoid, values);
+
+    assert_eq!(*index.total(), ((0..100).sum(), 99));
+
+    for &(min, max) in &[(40, 60), (0, 100), (10, 20), (97, 99)] {
+        let ids: Vec<_> = index.search_range(min, min, max, max).collect();
+        let expected =
+            (ids.iter().map(|id| *id as u64).sum(), ids.iter().cloned().max().unwrap_or(0));
+        assert_eq!(index.aggregate_range(min, min, max, max), expected);
+    }
+}
+
+#[test]
+fn dynamic_insert_and_remove() {
+    let mut index = DynamicFlatBush::new();
+
+    let mut boxes = Vec::new();
+    for copy in 0..10 {
+        for i in (0..DATA.len()).step_by(4) {
+            let b = [DATA[i], DATA[i + 1], DATA[i + 2], DATA[i + 3]];
+            let id = index.insert(b);
+            assert_eq!(id, boxes.len());
+            boxes.push(b);
+            if (id + copy) % 3 == 0 {
+                assert!(index.remove(id));
+            }
+        }
+    }
+    for id in (0..boxes.len()).step_by(7) {
+        index.remove(id);
+    }
+
+    let live: Vec<usize> = (0..boxes.len()).filter(|id| index.contains(*id)).collect();
+    assert_eq!(index.len(), live.len());
+
+    let mut result: Vec<_> = index.search_range(40, 40, 60, 60).collect();
+    let mut expected: Vec<_> = live
+        .iter()
+        .cloned()
+        .filter(|id| {
+            let b = boxes[*id];
+            b[0] <= 60 && b[1] <= 60 && b[2] >= 40 && b[3] >= 40
+        })
+        .collect();
+    result.sort();
+    expected.sort();
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn dynamic_query_variants() {
+    let mut index = DynamicFlatBush::new_with_buffer_size(4, 8);
+    let num_items = DATA.len() / 4;
+    for i in 0..num_items {
+        index.insert([DATA[4 * i], DATA[4 * i + 1], DATA[4 * i + 2], DATA[4 * i + 3]]);
+    }
+    for id in (0..num_items).step_by(3) {
+        index.remove(id);
+    }
+
+    let sorted = |mut ids: Vec<usize>| {
+        ids.sort();
+        ids
+    };
+    let range = sorted(index.search_range(40, 40, 60, 60).collect());
+
+    let mut visited = Vec::new();
+    let _ = index.visit_range::<(), _>(40, 40, 60, 60, |id| {
+        visited.push(id);
+        ControlFlow::Continue(())
+    });
+    assert_eq!(sorted(visited), range);
+
+    let mut scratch = QueryScratch::new();
+    assert_eq!(sorted(index.search_range_with(40, 40, 60, 60, &mut scratch).to_vec()), range);
+    let results = index.search_range_batch(&[[40, 40, 60, 60]]);
+    assert_eq!(sorted(results.get(0).to_vec()), range);
+
+    // removing most of the boxes rebuilds or drops the levels they were in
+    for id in 0..num_items - 6 {
+        index.remove(id);
+    }
+    let live: Vec<_> = (0..num_items).filter(|id| id % 3 != 0 && *id >= num_items - 6).collect();
+    assert_eq!(index.len(), live.len());
+    assert!(index.num_stored() < 15, "removed boxes are reclaimed");
+    assert_eq!(sorted(index.search_range(0, 0, 100, 100).collect()), live);
+}
+
+#[test]
+fn delete_and_compact() {
+    let mut index = create_index();
+
+    let all: Vec<_> = index.search_range(20, 20, 70, 70).collect();
+    for id in all.iter().step_by(2) {
+        assert!(index.delete(*id));
+    }
+    let num_deleted = all.iter().step_by(2).count();
+    assert!(!index.delete(all[0]));
+    assert!(index.is_deleted(all[0]));
+    assert_eq!(index.num_deleted(), num_deleted);
+
+    let mut expected: Vec<_> = all.iter().cloned().skip(1).step_by(2).collect();
+    expected.sort();
+
+    let check = |index: &FlatBush<u32>| {
+        let mut result: Vec<_> = index.search_range(20, 20, 70, 70).collect();
+        result.sort();
+        assert_eq!(result, expected);
+
+        let mut result = index.search_range_with(20, 20, 70, 70, &mut QueryScratch::new()).to_vec();
+        result.sort();
+        assert_eq!(result, expected);
+
+        let mut result = Vec::new();
+        let _ = index.visit_range(20, 20, 70, 70, |id| {
+            result.push(id);
+            ControlFlow::<()>::Continue(())
+        });
+        result.sort();
+        assert_eq!(result, expected);
+
+        assert_eq!(index.search_range(0, 0, 100, 100).count(), 100 - num_deleted);
+    };
+
+    check(&index);
+
+    let compacted = index.compact();
+    assert_eq!(compacted.num_items(), 100 - num_deleted);
+    assert_eq!(compacted.num_deleted(), 0);
+    check(&compacted);
+
+    // every remaining node still bounds its children
+    compacted.visit_nodes(|node| {
+        let bbox = node.bbox();
+        for child in node.children() {
+            let child_bbox = child.bbox();
+            assert!(
+                child_bbox[0] >= bbox[0]
+                    && child_bbox[1] >= bbox[1]
+                    && child_bbox[2] <= bbox[2]
+                    && child_bbox[3] <= bbox[3]
+            );
+        }
+        true
+    });
+}
+
+fn assert_nodes_fit_children(index: &FlatBush<u32>) {
+    index.visit_nodes(|node| {
+        if !node.is_item() {
+            let children: Vec<_> = node.children().map(|child| child.bbox()).collect();
+            let expected = [
+                children.iter().map(|b| b[0]).min().unwrap(),
+                children.iter().map(|b| b[1]).min().unwrap(),
+                children.iter().map(|b| b[2]).max().unwrap(),
+                children.iter().map(|b| b[3]).max().unwrap(),
+            ];
+            assert_eq!(node.bbox(), expected, "node box is the union of its children");
+        }
+        true
+    });
+}
+
+#[test]
+fn refits_moved_items() {
+    let mut index = create_index();
+    assert_eq!(index.refit_overhead(), 1.);
+
+    // move one item far away
+    assert!(index.refit(7, [200, 200, 210, 205]));
+    assert_nodes_fit_children(&index);
+    assert_eq!(index.bounds(), [0, 1, 210, 205]);
+    assert_eq!(index.search_range(199, 199, 300, 300).collect::<Vec<_>>(), [7]);
+    assert!(!index.search_range(0, 0, 100, 100).any(|id| id == 7));
+    assert!(index.refit_overhead() > 1.);
+
+    // shift everything, and check that queries see the new positions
+    index.refit_all(|id| {
+        let pos = 4 * id;
+        [DATA[pos] + 1000, DATA[pos + 1] + 1000, DATA[pos + 2] + 1000, DATA[pos + 3] + 1000]
+    });
+    assert_nodes_fit_children(&index);
+    assert_eq!(index.bounds(), [1000, 1001, 1096, 1095]);
+
+    let mut results: Vec<_> = index.search_range(1040, 1040, 1060, 1060).collect();
+    let mut expected: Vec<_> = create_index().search_range(40, 40, 60, 60).collect();
+    results.sort();
+    expected.sort();
+    assert_eq!(results, expected);
+    // a uniform shift doesn't change the shape of the tree
+    assert_eq!(index.refit_overhead(), 1.);
+}
+
+#[test]
+fn looks_up_boxes_by_id() {
+    for &id_lookup in &[false, true] {
+        let mut builder = FlatBushBuilder::new();
+        builder.set_id_lookup(id_lookup);
+        for i in (0..DATA.len()).step_by(4) {
+            builder.add([DATA[i], DATA[i + 1], DATA[i + 2], DATA[i + 3]]);
+        }
+        let mut index = builder.finish();
+        assert_eq!(index.positions.is_some(), id_lookup);
+
+        for id in 0..(DATA.len() / 4) {
+            let pos = 4 * id;
+            assert_eq!(
+                index.bbox(id),
+                Some([DATA[pos], DATA[pos + 1], DATA[pos + 2], DATA[pos + 3]])
+            );
+        }
+        assert_eq!(index.bbox(DATA.len() / 4), None);
+
+        let ids: Vec<_> = index.iter().map(|(id, _)| id as u32).collect();
+        assert_eq!(ids, index.indices.iter().take(DATA.len() / 4).collect::<Vec<_>>());
+        assert!(index.iter().all(|(id, b)| index.bbox(id) == Some(b)));
+
+        assert!(index.refit(5, [1, 2, 3, 4]));
+        assert_eq!(index.bbox(5), Some([1, 2, 3, 4]));
+        assert!(!index.refit(DATA.len() / 4, [1, 2, 3, 4]));
+
+        index.delete(3);
+        assert_eq!(index.bbox(3), None);
+        assert_eq!(index.iter().count(), DATA.len() / 4 - 1);
+
+        let mut compacted = index.compact();
+        assert_eq!(compacted.positions.is_some(), id_lookup);
+        assert_eq!(compacted.bbox(3), None);
+        assert_eq!(compacted.bbox(5), Some([1, 2, 3, 4]));
+
+        // ids left out by compacting can't be refit, and leave the tree alone
+        let boxes = compacted.boxes.clone();
+        assert!(!compacted.refit(3, [1000, 1000, 1001, 1001]));
+        assert_eq!(compacted.boxes, boxes);
+        assert_eq!(compacted.refit_overhead(), 1.);
+    }
+}
+
+#[test]
+fn performs_bbox_search_with_boxes() {
+    let index = create_index();
+
+    let results: Vec<_> = index.search_range_boxes(40, 40, 60, 60).collect();
+    let mut ids: Vec<_> = results.iter().map(|(id, _)| *id).collect();
+    let mut expected: Vec<_> = index.search_range(40, 40, 60, 60).collect();
+    ids.sort();
+    expected.sort();
+    assert_eq!(ids, expected);
+
+    for (id, b) in results {
+        let pos = 4 * id;
+        assert_eq!(b, [DATA[pos], DATA[pos + 1], DATA[pos + 2], DATA[pos + 3]]);
+    }
+}
+
+#[test]
+fn finds_nearest_boxes_geographically() {
+    // a grid of points and small boxes covering the globe, including across the antimeridian
+    let mut boxes = Vec::new();
+    for lon in (-180..180).step_by(10) {
+        for lat in (-80..=80).step_by(10) {
+            let (lon, lat) = (lon as f64 + 0.5, lat as f64 + 0.5);
+            boxes.push([lon, lat, lon + (lat.abs() % 3.), lat + (lon.abs() % 2.)]);
+        }
+    }
+    let index: FlatBush<f64> = boxes.iter().collect();
+
+    // compare against a brute-force search, using the distance to the nearest of a dense sample of
+    // points from each box
+    let brute_force = |lon: f64, lat: f64| {
+        let mut dists: Vec<(usize, f64)> = boxes
+            .iter()
+            .enumerate()
+            .map(|(id, b)| {
+                let mut min_dist = f64::INFINITY;
+                for i in 0..=20 {
+                    for j in 0..=20 {
+                        let x = b[0] + (b[2] - b[0]) * (i as f64) / 20.;
+                        let y = b[1] + (b[3] - b[1]) * (j as f64) / 20.;
+                        min_dist = min_dist.min(crate::geo::distance(lon, lat, x, y));
+                    }
+                }
+                (id, min_dist)
+            })
+            .collect();
+        dists.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        dists
+    };
+
+    for &(lon, lat) in &[(0., 0.), (179.9, 12.), (-179.9, -33.), (100., 85.), (-73.9, 40.7)] {
+        let expected = brute_force(lon, lat);
+
+        let results = index.geo_around(lon, lat, 10, f64::INFINITY);
+        assert_eq!(results.len(), 10);
+        for ((_, dist), (_, expected_dist)) in results.iter().zip(expected.iter()) {
+            // the sampled distances can only overshoot the true distance to the box
+            assert!(*dist <= expected_dist + 1e-6, "{} <= {}", dist, expected_dist);
+            assert!(expected_dist - dist < 20., "{} ~ {}", dist, expected_dist);
+        }
+        assert!(results.windows(2).all(|w| w[0].1 <= w[1].1), "results are sorted by distance");
+
+        let max_distance = expected[5].1 + 1.;
+        let results = index.geo_around(lon, lat, usize::MAX, max_distance);
+        assert!(results.iter().all(|(_, dist)| *dist <= max_distance));
+        assert!(results.len() >= 6);
+    }
+
+    // a query just west of the antimeridian finds boxes just east of it
+    let results = index.geo_around(179.9, 0.4, 1, f64::INFINITY);
+    assert_eq!(boxes[results[0].0][0], -179.5);
+}
+
+#[test]
+fn searches_range_across_antimeridian() {
+    let boxes = [
+        [170., 0., 175., 5.],
+        [178., 0., -178., 5.], // crosses the antimeridian
+        [-175., 0., -170., 5.],
+        [-5., 0., 5., 5.],
+        [160., 10., -160., 20.], // crosses, but out of the latitude range
+    ];
+    let mut builder = FlatBushBuilder::new_with_node_size(2);
+    for b in boxes.iter() {
+        builder.add_geo(*b);
+    }
+    let index = builder.finish();
+
+    let search = |min_lon, max_lon| {
+        let mut results: Vec<_> = index.geo_search_range(min_lon, 1., max_lon, 2.).collect();
+        results.sort_unstable();
+        results
+    };
+
+    assert_eq!(search(172., -172.), vec![0, 1, 2]);
+    assert_eq!(search(179., -179.), vec![1]);
+    assert_eq!(search(179., 180.), vec![1]);
+    assert_eq!(search(-180., -179.), vec![1]);
+    assert_eq!(search(-176., 176.), vec![0, 2, 3]);
+    assert_eq!(search(-178.5, -177.), vec![1]);
+    assert_eq!(search(-1., 1.), vec![3]);
+    assert_eq!(search(-100., 100.), vec![3]);
+
+    // boxes crossing the antimeridian are stored unwrapped
+    assert_eq!(index.bbox(1), Some([178., 0., 182., 5.]));
+}
+
+#[test]
+fn searches_by_containment() {
+    let index = create_index();
+    let boxes: Vec<[u32; 4]> = DATA.chunks(4).map(|b| b.try_into().unwrap()).collect();
+
+    let query = [20, 30, 50, 70];
+    let mut results: Vec<_> = index.search_contained_in(20, 30, 50, 70).collect();
+    results.sort_unstable();
+    let expected: Vec<_> = (0..boxes.len())
+        .filter(|&i| {
+            let b = boxes[i];
+            b[0] >= query[0] && b[1] >= query[1] && b[2] <= query[2] && b[3] <= query[3]
+        })
+        .collect();
+    assert!(!expected.is_empty());
+    assert_eq!(results, expected);
+
+    let query = [49, 54, 51, 55];
+    let mut results: Vec<_> = index.search_containing(49, 54, 51, 55).collect();
+    results.sort_unstable();
+    let expected: Vec<_> = (0..boxes.len())
+        .filter(|&i| {
+            let b = boxes[i];
+            b[0] <= query[0] && b[1] <= query[1] && b[2] >= query[2] && b[3] >= query[3]
+        })
+        .collect();
+    assert!(!expected.is_empty());
+    assert_eq!(results, expected);
+}
+
+#[test]
+fn searches_by_containment_through_inside_nodes() {
+    // a 4x4 grid of unit boxes, two apart; with 4 items per node, each quadrant of the grid
+    // packs into its own node
+    let mut builder = FlatBushBuilder::new_with_node_size(4);
+    for j in 0..4 {
+        for i in 0..4 {
+            builder.add([2 * i, 2 * j, 2 * i + 1, 2 * j + 1]);
+        }
+    }
+    let mut index: FlatBush<u32> = builder.finish();
+
+    let quadrant = [0, 0, 3, 3];
+    let is_node = (index.num_items..index.boxes.len() / 4)
+        .any(|i| index.boxes[(4 * i)..(4 * i + 4)] == quadrant);
+    assert!(is_node, "the lower left quadrant should be a node");
+
+    let search = |index: &FlatBush<u32>, min_x, min_y, max_x, max_y| {
+        let mut results: Vec<_> = index.search_contained_in(min_x, min_y, max_x, max_y).collect();
+        results.sort_unstable();
+        results
+    };
+
+    assert_eq!(search(&index, 0, 0, 3, 3), vec![0, 1, 4, 5]);
+    assert_eq!(search(&index, 0, 0, 4, 3), vec![0, 1, 4, 5]);
+    assert_eq!(search(&index, 0, 0, 5, 3), vec![0, 1, 2, 4, 5, 6]);
+    assert_eq!(search(&index, 0, 0, 7, 7), (0..16).collect::<Vec<_>>());
+    assert_eq!(search(&index, 1, 0, 3, 3), vec![1, 5]);
+
+    // deleted items under an inside node are still skipped
+    index.delete(4);
+    assert_eq!(search(&index, 0, 0, 3, 3), vec![0, 1, 5]);
+}
+
+#[test]
+fn casts_rays_front_to_back() {
+    let index = create_index();
+    let boxes: Vec<[u32; 4]> = DATA.chunks(4).map(|b| b.try_into().unwrap()).collect();
+
+    // a horizontal ray enters each box it hits at the box's left edge
+    let results: Vec<_> = index.raycast([10, 50], [1., 0.], 80.).collect();
+    let mut expected: Vec<_> = (0..boxes.len())
+        .filter(|&i| {
+            boxes[i][1] <= 50 && boxes[i][3] >= 50 && boxes[i][2] >= 10 && boxes[i][0] <= 90
+        })
+        .map(|i| (i, (boxes[i][0].max(10) - 10) as f64))
+        .collect();
+    assert!(!expected.is_empty());
+    assert!(results.windows(2).all(|w| w[0].1 <= w[1].1), "results are front to back");
+    let mut results = results;
+    results.sort_by_key(|&(id, _)| id);
+    expected.sort_by_key(|&(id, _)| id);
+    assert_eq!(results, expected);
+
+    // the first hit can be taken without searching the rest
+    assert_eq!(
+        index.raycast([10, 50], [1., 0.], 80.).next().map(|(_, t)| t),
+        Some(expected.iter().map(|&(_, t)| t).fold(f64::INFINITY, f64::min))
+    );
+
+    // every box that a point on the segment falls into is found
+    let results: Vec<_> = index.search_segment(5, 90, 95, 10).collect();
+    for i in 0..=900 {
+        let x = 5. + 90. * (i as f64) / 900.;
+        let y = 90. - 80. * (i as f64) / 900.;
+        for (id, b) in boxes.iter().enumerate() {
+            if b[0] as f64 <= x && x <= b[2] as f64 && b[1] as f64 <= y && y <= b[3] as f64 {
+                assert!(results.contains(&id), "{} at ({}, {})", id, x, y);
+            }
+        }
+    }
+    let mut dedup = results.clone();
+    dedup.sort_unstable();
+    dedup.dedup();
+    assert_eq!(dedup.len(), results.len());
+}
+
+#[test]
+fn searches_boxes_containing_point() {
+    let boxes =
+        [[0, 0, 100, 100], [40, 40, 60, 60], [45, 45, 50, 55], [60, 60, 70, 70], [0, 0, 10, 10]];
+    let mut builder = FlatBushBuilder::new_with_node_size(2);
+    builder.extend(boxes.iter());
+    let index = builder.finish();
+
+    let mut results: Vec<_> = index.search_point(50, 50).collect();
+    results.sort_unstable();
+    assert_eq!(results, vec![0, 1, 2]);
+    assert_eq!(index.search_point_by_area(50, 50), vec![2, 1, 0]);
+
+    // edges count as inside
+    let mut results: Vec<_> = index.search_point(60, 60).collect();
+    results.sort_unstable();
+    assert_eq!(results, vec![0, 1, 3]);
+    assert_eq!(index.search_point_by_area(60, 60), vec![3, 1, 0]);
+
+    assert_eq!(index.search_point(101, 50).count(), 0);
+}
+
+#[test]
+fn refines_polygon_hits() {
+    let square_with_hole: Vec<Vec<[f64; 2]>> = vec![
+        vec![[0., 0.], [10., 0.], [10., 10.], [0., 10.]],
+        vec![[4., 4.], [6., 4.], [6., 6.], [4., 6.], [4., 4.]],
+    ];
+    let triangle = vec![vec![[20., 0.], [30., 0.], [20., 10.]]];
+    let diamond = vec![vec![[50., 40.], [60., 50.], [50., 60.], [40., 50.]]];
+
+    let mut builder = PolygonIndexBuilder::new_with_node_size(2);
+    assert_eq!(builder.add(&square_with_hole), 0);
+    assert_eq!(builder.add(&triangle), 1);
+    assert_eq!(builder.add(&diamond), 2);
+    let index = builder.finish();
+    assert_eq!(index.len(), 3);
+
+    let search_point = |x, y| {
+        let mut results: Vec<_> = index.search_point(x, y).collect();
+        results.sort_unstable();
+        results
+    };
+    assert_eq!(search_point(1., 1.), vec![0]);
+    assert_eq!(search_point(5., 5.), Vec::<usize>::new(), "inside the hole");
+    assert_eq!(search_point(21., 1.), vec![1]);
+    assert_eq!(search_point(29., 9.), Vec::<usize>::new(), "inside the bbox only");
+    assert_eq!(search_point(50., 50.), vec![2]);
+    assert_eq!(search_point(41., 41.), Vec::<usize>::new(), "inside the bbox only");
+
+    let search_range = |min_x, min_y, max_x, max_y| {
+        let mut results: Vec<_> = index.search_range(min_x, min_y, max_x, max_y).collect();
+        results.sort_unstable();
+        results
+    };
+    assert_eq!(search_range(4.5, 4.5, 5.5, 5.5), Vec::<usize>::new(), "inside the hole");
+    assert_eq!(search_range(3., 3., 5., 5.), vec![0], "crosses the hole's edge");
+    assert_eq!(search_range(1., 1., 2., 2.), vec![0], "inside the polygon");
+    assert_eq!(search_range(-5., -5., 100., 100.), vec![0, 1, 2], "contains the polygons");
+    assert_eq!(search_range(27., 7., 29., 9.), Vec::<usize>::new(), "past the hypotenuse");
+    assert_eq!(search_range(24., 4., 29., 9.), vec![1], "across the hypotenuse");
+    assert_eq!(search_range(40., 40., 44., 44.), Vec::<usize>::new(), "in the diamond's corner");
+
+    let rings: Vec<_> = index.rings(1).unwrap().collect();
+    assert_eq!(rings, vec![&triangle[0][..]]);
+    assert_eq!(index.rings(0).unwrap().count(), 2);
+    assert!(index.rings(3).is_none());
+}
+
+#[test]
+fn snaps_to_nearest_segments() {
+    // a grid of horizontal and vertical road segments, 10 units apart
+    let mut segments = Vec::new();
+    for i in 0..10 {
+        for j in 0..10 {
+            let (x, y) = (i as f64 * 10., j as f64 * 10.);
+            segments.push(([x, y], [x + 10., y]));
+            segments.push(([x, y], [x, y + 10.]));
+        }
+    }
+    let mut builder = SegmentIndexBuilder::new_with_node_size(4);
+    for (a, b) in segments.iter() {
+        builder.add(*a, *b);
+    }
+    let index = builder.finish();
+    assert_eq!(index.len(), segments.len());
+
+    let results = index.nearest(33., 36., 1, f64::INFINITY);
+    assert_eq!(results.len(), 1);
+    let snap = results[0];
+    assert_eq!(segments[snap.id], ([30., 30.], [30., 40.]));
+    assert_eq!(snap.point, [30., 36.]);
+    assert!((snap.distance - 3.).abs() < 1e-9);
+    assert!((snap.fraction - 0.6).abs() < 1e-9);
+
+    // matches a brute-force search
+    let (x, y) = (47.5, 12.25);
+    let results = index.nearest(x, y, 5, f64::INFINITY);
+    let mut expected: Vec<f64> = segments
+        .iter()
+        .map(|(a, b)| {
+            let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+            let t = (((x - a[0]) * dx + (y - a[1]) * dy) / (dx * dx + dy * dy)).clamp(0., 1.);
+            ((a[0] + t * dx - x).powi(2) + (a[1] + t * dy - y).powi(2)).sqrt()
+        })
+        .collect();
+    expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(results.len(), 5);
+    for (snap, dist) in results.iter().zip(expected.iter()) {
+        assert!((snap.distance - dist).abs() < 1e-9);
+    }
+
+    // nothing is returned beyond the maximum distance
+    let results = index.nearest(150., 150., 3, 10.);
+    assert!(results.is_empty());
+    let results = index.nearest(105., 50., 10, 5.);
+    assert!(results.iter().all(|snap| snap.distance <= 5.));
+    assert!(!results.is_empty());
+}
+
+#[test]
+fn performs_polygon_search() {
+    let index = create_index();
+    let boxes: Vec<[u32; 4]> = DATA.chunks(4).map(|b| b.try_into().unwrap()).collect();
+
+    // a rectangle finds the same items as a box search
+    let rectangle = [[20, 30], [50, 30], [50, 70], [20, 70]];
+    let mut results: Vec<_> = index.search_polygon(&rectangle).collect();
+    results.sort_unstable();
+    let mut expected: Vec<_> = index.search_range(20, 30, 50, 70).collect();
+    expected.sort_unstable();
+    assert_eq!(results, expected);
+
+    // the polygons below only have axis-aligned edges, so which boxes they hit can be worked out
+    // with plain box comparisons
+    let overlaps = |b: [u32; 4], min_x, min_y, max_x, max_y| {
+        b[0] <= max_x && b[1] <= max_y && b[2] >= min_x && b[3] >= min_y
+    };
+    let search = |polygon: &[[u32; 2]]| {
+        let mut results: Vec<_> = index.search_polygon(polygon).collect();
+        results.sort_unstable();
+        results
+    };
+
+    // a concave U shape, closed: the union of its two arms and the base joining them
+    let u_shape =
+        [[20, 20], [80, 20], [80, 80], [60, 80], [60, 40], [40, 40], [40, 80], [20, 80], [20, 20]];
+    let expected: Vec<_> = (0..boxes.len())
+        .filter(|&i| {
+            let b = boxes[i];
+            overlaps(b, 20, 20, 40, 80)
+                || overlaps(b, 60, 20, 80, 80)
+                || overlaps(b, 20, 20, 80, 40)
+        })
+        .collect();
+    assert!(expected.len() < index.search_range(20, 20, 80, 80).count());
+    assert_eq!(search(&u_shape), expected);
+
+    // a square with a square hole, drawn as a single ring by cutting in along y = 50; boxes
+    // strictly inside the hole are missed, everything else touching the outer square is hit
+    let holed = [
+        [20, 20],
+        [80, 20],
+        [80, 80],
+        [20, 80],
+        [20, 50],
+        [40, 50],
+        [40, 60],
+        [60, 60],
+        [60, 40],
+        [40, 40],
+        [40, 50],
+        [20, 50],
+    ];
+    let in_hole = |b: [u32; 4]| b[0] > 40 && b[1] > 40 && b[2] < 60 && b[3] < 60;
+    let expected: Vec<_> = (0..boxes.len())
+        .filter(|&i| overlaps(boxes[i], 20, 20, 80, 80) && !in_hole(boxes[i]))
+        .collect();
+    assert!((0..boxes.len()).any(|i| in_hole(boxes[i])));
+    assert_eq!(search(&holed), expected);
+}
+
+#[test]
+fn performs_oriented_rect_search() {
+    let index = create_index();
+    let boxes: Vec<[u32; 4]> = DATA.chunks(4).map(|b| b.try_into().unwrap()).collect();
+
+    let rect = OrientedRect::new([50., 50.], [80., 20.], std::f64::consts::FRAC_PI_4);
+    let mut results: Vec<_> = index.search_oriented(rect).collect();
+    results.sort_unstable();
+
+    // check each box against the rectangle's outline, as a polygon
+    let outline = rect.corners();
+    let expected: Vec<_> = (0..boxes.len())
+        .filter(|&i| {
+            let b =
+                [boxes[i][0] as f64, boxes[i][1] as f64, boxes[i][2] as f64, boxes[i][3] as f64];
+            polygon::ring_crosses_box(&outline, &b) || polygon::ring_contains(&outline, b[0], b[1])
+        })
+        .collect();
+    assert!(!expected.is_empty());
+    assert_eq!(results, expected);
+    assert!(results.len() < index.search_range(15, 15, 85, 85).count());
+}
+
+#[test]
+fn packs_with_other_curves() {
+    // orders items by x alone, which makes for long, thin nodes
+    struct ByX;
+    impl SpaceFillingCurve for ByX {
+        fn key(&self, x: f64, _y: f64, width: f64, _height: f64) -> u64 {
+            (x / width * u32::MAX as f64) as u64
+        }
+    }
+
+    fn build<C: SpaceFillingCurve + Send + Sync + 'static>(curve: Option<C>) -> FlatBush<u32> {
+        let mut builder = FlatBushBuilder::new_with_node_size(4);
+        if let Some(curve) = curve {
+            builder.set_curve(curve);
+        }
+        builder.extend(DATA.chunks(4).map(|b| -> [u32; 4] { b.try_into().unwrap() }));
+        builder.finish()
+    }
+    let indexes = [
+        build::<Hilbert>(None),
+        build(Some(Hilbert)),
+        build(Some(Hilbert32)),
+        build(Some(Morton)),
+        build(Some(ByX)),
+    ];
+
+    // Hilbert is the default
+    assert_eq!(indexes[0].iter().collect::<Vec<_>>(), indexes[1].iter().collect::<Vec<_>>());
+
+    for index in indexes.iter() {
+        assert_nodes_fit_children(index);
+        for query in [[40, 40, 60, 60], [0, 0, 30, 100], [70, 10, 95, 25]].iter() {
+            let mut results: Vec<_> =
+                index.search_range(query[0], query[1], query[2], query[3]).collect();
+            results.sort_unstable();
+            let mut expected: Vec<_> =
+                indexes[0].search_range(query[0], query[1], query[2], query[3]).collect();
+            expected.sort_unstable();
+            assert_eq!(results, expected);
+        }
+    }
+
+    // items come out in the custom curve's order
+    let xs: Vec<_> = indexes[4].iter().map(|(_, b)| b[0] + b[2]).collect();
+    assert!(xs.chunks(4).zip(xs.chunks(4).skip(1)).all(|(a, b)| a.iter().max() <= b.iter().min()));
+}
+
+#[test]
+fn default_packing_order_is_stable() {
+    // the order items were packed in before curves were configurable, which existing indexes
+    // (and anything that depends on their layout) were built with
+    let index = create_index();
+    let order: Vec<_> = (0..index.num_items).map(|i| index.indices.get(i)).collect();
+    assert_eq!(
+        order,
+        [
+            95, 92, 87, 70, 67, 64, 55, 52, 49, 43, 40, 11, 26, 19, 44, 9, 59, 84, 77, 39, 6, 75,
+            80, 18, 23, 62, 58, 88, 86, 27, 90, 0, 73, 7, 37, 30, 13, 14, 48, 17, 56, 79, 25, 38,
+            85, 76, 91, 66, 24, 33, 21, 3, 99, 16, 54, 28, 29, 68, 50, 31, 22, 72, 78, 83, 53, 89,
+            51, 93, 81, 20, 8, 96, 4, 63, 74, 5, 47, 32, 10, 98, 61, 82, 57, 97, 65, 35, 41, 2, 45,
+            46, 36, 42, 69, 34, 1, 60, 15, 94, 12, 71
+        ]
+    );
+
+    // centers on the far edges, where scaling into the unit square first and scaling by the
+    // grid size first round to different cells
+    let edges = [
+        [1.09999, 0.],
+        [1.09999, 1.09999],
+        [0., 1.1],
+        [1.1, 0.],
+        [0., 0.],
+        [0.55, 0.55],
+        [1.1, 1.1],
+        [0., 1.09999],
+    ];
+    for &explicit in &[false, true] {
+        // and setting the `Hilbert` curve explicitly packs the same way as leaving the default
+        let mut builder = FlatBushBuilder::new_with_node_size(2);
+        if explicit {
+            builder.set_curve(Hilbert);
+        }
+        for p in edges.iter() {
+            builder.add([p[0], p[1], p[0], p[1]]);
+        }
+        let index = builder.finish();
+        let order: Vec<_> = (0..index.num_items).map(|i| index.indices.get(i)).collect();
+        assert_eq!(order, [4, 5, 2, 7, 1, 6, 3, 0]);
+    }
+}
+
+#[test]
+fn finds_nearest_boxes_across_antimeridian() {
+    let mut builder = FlatBushBuilder::new_with_node_size(2);
+    builder.add_geo([179., 0., -179., 1.]);
+    builder.add([-179.5, 0., -179.4, 0.1]);
+    builder.add_geo([170., 10., -170., 20.]);
+    builder.add([10., 0., 11., 1.]);
+    let index = builder.finish();
+
+    // inside the crossing box, on its western half
+    let results = index.geo_around(-179.9, 0.5, 2, f64::INFINITY);
+    assert_eq!(results[0], (0, 0.));
+    assert_eq!(results[1].0, 1);
+
+    // and on its eastern half
+    let results = index.geo_around(179.5, 0.5, 1, f64::INFINITY);
+    assert_eq!(results, vec![(0, 0.)]);
+
+    // a point inside the other crossing box isn't pruned along with its parent
+    let results = index.geo_around(-175., 15., 1, 1.);
+    assert_eq!(results, vec![(2, 0.)]);
+}
+
+#[test]
+fn ignores_deleting_unknown_ids() {
+    for &id_lookup in &[false, true] {
+        let mut builder = FlatBushBuilder::new();
+        builder.set_id_lookup(id_lookup);
+        builder.extend([[1, 1, 2, 2], [3, 3, 4, 4]].iter());
+        let mut index = builder.finish();
+
+        assert!(!index.delete(1000));
+        assert!(!index.delete(2));
+        assert_eq!(index.num_deleted(), 0);
+        assert_eq!(index.deleted_ratio(), 0.);
+
+        // ids dropped by a compaction aren't in the index anymore either
+        assert!(index.delete(0));
+        let mut compacted = index.compact();
+        assert!(!compacted.delete(0));
+        assert_eq!(compacted.num_deleted(), 0);
+        assert!(compacted.delete(1));
+        assert_eq!(compacted.deleted_ratio(), 1.);
+    }
+}
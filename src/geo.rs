@@ -0,0 +1,90 @@
+// helpers for working with longitude/latitude coordinates, ported from geokdbush/geoflatbush
+
+pub const EARTH_RADIUS_KM: f64 = 6371.;
+
+const RAD: f64 = std::f64::consts::PI / 180.;
+
+pub(crate) fn cos_lat(lat: f64) -> f64 {
+    (lat * RAD).cos()
+}
+
+/// Great-circle distance in kilometres between two lon/lat points, in degrees.
+pub fn distance(lon: f64, lat: f64, lon2: f64, lat2: f64) -> f64 {
+    let haver_sin_d_lon = haver_sin((lon - lon2) * RAD);
+    haver_sin_to_km(haver_sin_dist_partial(haver_sin_d_lon, cos_lat(lat), lat, lat2))
+}
+
+// convert a distance in km into the haversine value that the distance calculations compare
+pub(crate) fn km_to_haver_sin(km: f64) -> f64 {
+    if km >= std::f64::consts::PI * EARTH_RADIUS_KM {
+        // anything on the globe is in range
+        f64::INFINITY
+    } else {
+        haver_sin(km / EARTH_RADIUS_KM)
+    }
+}
+
+pub(crate) fn haver_sin_to_km(h: f64) -> f64 {
+    2. * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+// lower bound on the haversine distance from a point to any point of a lon/lat box, which also
+// accounts for the box being closer across the antimeridian
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn box_dist(
+    lon: f64,
+    lat: f64,
+    cos_lat: f64,
+    min_lon: f64,
+    min_lat: f64,
+    max_lon: f64,
+    max_lat: f64,
+) -> f64 {
+    // query point is between minimum and maximum longitudes; boxes that cross the antimeridian
+    // are stored with their maximum longitude past 180, so check the point a turn either way too
+    let in_lon_range = |lon: f64| lon >= min_lon && lon <= max_lon;
+    if in_lon_range(lon) || in_lon_range(lon + 360.) || in_lon_range(lon - 360.) {
+        if lat < min_lat {
+            return haver_sin((lat - min_lat) * RAD);
+        }
+        if lat > max_lat {
+            return haver_sin((lat - max_lat) * RAD);
+        }
+        return 0.;
+    }
+
+    // query point is west or east of the bounding box;
+    // calculate the extremum for great circle distance from query point to the closest longitude
+    let haver_sin_d_lon = haver_sin((lon - min_lon) * RAD).min(haver_sin((lon - max_lon) * RAD));
+    let extremum_lat = vertex_lat(lat, haver_sin_d_lon);
+
+    // if extremum is inside the box, return the distance to it
+    if extremum_lat > min_lat && extremum_lat < max_lat {
+        return haver_sin_dist_partial(haver_sin_d_lon, cos_lat, lat, extremum_lat);
+    }
+    // otherwise return the distance to one of the bbox corners (whichever is closest)
+    let to_min_lat = haver_sin_dist_partial(haver_sin_d_lon, cos_lat, lat, min_lat);
+    let to_max_lat = haver_sin_dist_partial(haver_sin_d_lon, cos_lat, lat, max_lat);
+    to_min_lat.min(to_max_lat)
+}
+
+#[inline(always)]
+fn haver_sin(theta: f64) -> f64 {
+    let s = (theta / 2.).sin();
+    s * s
+}
+
+#[inline(always)]
+fn haver_sin_dist_partial(haver_sin_d_lon: f64, cos_lat1: f64, lat1: f64, lat2: f64) -> f64 {
+    cos_lat1 * (lat2 * RAD).cos() * haver_sin_d_lon + haver_sin((lat1 - lat2) * RAD)
+}
+
+// latitude of the point along the great circle through the query point and the box edge's
+// longitude that's closest to the query point
+fn vertex_lat(lat: f64, haver_sin_d_lon: f64) -> f64 {
+    let cos_d_lon = 1. - 2. * haver_sin_d_lon;
+    if cos_d_lon <= 0. {
+        return if lat > 0. { 90. } else { -90. };
+    }
+    ((lat * RAD).tan() / cos_d_lon).atan() / RAD
+}
@@ -0,0 +1,163 @@
+// f64 geometry helpers shared by the queries that work with segments, rays and boxes; boxes are
+// `[min_x, min_y, max_x, max_y]` and segments are `[x0, y0, x1, y1]`
+
+use num_traits::AsPrimitive;
+
+// slab test: the parameter at which the ray enters the box, if it does so before `max_t`
+pub(crate) fn slab_entry<T: AsPrimitive<f64>>(
+    origin: [f64; 2],
+    dir: [f64; 2],
+    max_t: f64,
+    b: &[T],
+) -> Option<f64> {
+    let mut t_enter: f64 = 0.;
+    let mut t_exit: f64 = max_t;
+
+    for axis in 0..2 {
+        let lo: f64 = b[axis].as_();
+        let hi: f64 = b[axis + 2].as_();
+
+        if dir[axis] == 0. {
+            // parallel to this slab, so either always or never inside it
+            if origin[axis] < lo || origin[axis] > hi {
+                return None;
+            }
+            continue;
+        }
+
+        let inv = 1. / dir[axis];
+        let mut t0 = (lo - origin[axis]) * inv;
+        let mut t1 = (hi - origin[axis]) * inv;
+        if inv < 0. {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+
+        t_enter = t_enter.max(t0);
+        t_exit = t_exit.min(t1);
+        if t_enter > t_exit {
+            return None;
+        }
+    }
+
+    Some(t_enter)
+}
+
+// the closest point on the segment to `p`, and how far along the segment it is
+pub(crate) fn project<T: AsPrimitive<f64>>(p: [f64; 2], segment: [T; 4]) -> ([f64; 2], f64) {
+    let [x0, y0, x1, y1]: [f64; 4] =
+        [segment[0].as_(), segment[1].as_(), segment[2].as_(), segment[3].as_()];
+    let (dx, dy) = (x1 - x0, y1 - y0);
+
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 == 0. { 0. } else { ((p[0] - x0) * dx + (p[1] - y0) * dy) / len2 };
+    let t = t.clamp(0., 1.);
+
+    ([x0 + t * dx, y0 + t * dy], t)
+}
+
+pub(crate) fn sq_dist(a: [f64; 2], b: [f64; 2]) -> f64 {
+    let dx = a[0] - b[0];
+    let dy = a[1] - b[1];
+    dx * dx + dy * dy
+}
+
+// squared distance from `p` to the nearest point of the box
+pub(crate) fn box_sq_dist<T: AsPrimitive<f64>>(p: [f64; 2], b: &[T]) -> f64 {
+    let dx = axis_dist(p[0], b[0].as_(), b[2].as_());
+    let dy = axis_dist(p[1], b[1].as_(), b[3].as_());
+    dx * dx + dy * dy
+}
+
+fn axis_dist(k: f64, min: f64, max: f64) -> f64 {
+    if k < min {
+        min - k
+    } else if k <= max {
+        0.
+    } else {
+        k - max
+    }
+}
+
+// squared distance between a segment and the nearest point of a box, 0 if they touch
+pub(crate) fn segment_box_sq_dist<T: AsPrimitive<f64>>(segment: [f64; 4], b: &[T]) -> f64 {
+    let [x0, y0, x1, y1] = segment;
+    if slab_entry([x0, y0], [x1 - x0, y1 - y0], 1., b).is_some() {
+        return 0.;
+    }
+
+    // otherwise the closest pair of points includes an endpoint of the segment or a corner of
+    // the box
+    let (min_x, min_y, max_x, max_y) = (b[0].as_(), b[1].as_(), b[2].as_(), b[3].as_());
+    let corners = [[min_x, min_y], [max_x, min_y], [max_x, max_y], [min_x, max_y]];
+    let to_corners = corners.iter().map(|&c| sq_dist(c, project(c, segment).0));
+    let ends = [[x0, y0], [x1, y1]];
+    let to_ends = ends.iter().map(|&p| box_sq_dist(p, b));
+
+    to_corners.chain(to_ends).fold(f64::INFINITY, f64::min)
+}
+
+/// A rectangle rotated about its center, for the `search_oriented` queries.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrientedRect {
+    center: [f64; 2],
+    half_size: [f64; 2],
+    // unit vectors along the rectangle's width and height
+    axes: [[f64; 2]; 2],
+}
+
+impl OrientedRect {
+    /// A rectangle of the given width and height (`size`), centered on `center` and rotated
+    /// counter-clockwise by `angle` radians.
+    pub fn new(center: [f64; 2], size: [f64; 2], angle: f64) -> OrientedRect {
+        let (sin, cos) = angle.sin_cos();
+        OrientedRect {
+            center,
+            half_size: [size[0] / 2., size[1] / 2.],
+            axes: [[cos, sin], [-sin, cos]],
+        }
+    }
+
+    /// The rectangle's corners, counter-clockwise.
+    pub fn corners(&self) -> [[f64; 2]; 4] {
+        let (c, [hw, hh], [u, v]) = (self.center, self.half_size, self.axes);
+        let corner = |su: f64, sv: f64| {
+            [c[0] + su * hw * u[0] + sv * hh * v[0], c[1] + su * hw * u[1] + sv * hh * v[1]]
+        };
+        [corner(-1., -1.), corner(1., -1.), corner(1., 1.), corner(-1., 1.)]
+    }
+
+    /// The axis-aligned bounding box of the rectangle, as `[min_x, min_y, max_x, max_y]`.
+    pub fn bbox(&self) -> [f64; 4] {
+        let (c, [hw, hh], [u, v]) = (self.center, self.half_size, self.axes);
+        let ex = hw * u[0].abs() + hh * v[0].abs();
+        let ey = hw * u[1].abs() + hh * v[1].abs();
+        [c[0] - ex, c[1] - ey, c[0] + ex, c[1] + ey]
+    }
+
+    pub(crate) fn contains(&self, x: f64, y: f64) -> bool {
+        let (dx, dy) = (x - self.center[0], y - self.center[1]);
+        self.axes
+            .iter()
+            .zip(self.half_size.iter())
+            .all(|(a, h)| (dx * a[0] + dy * a[1]).abs() <= *h)
+    }
+
+    // separating axis test against a box: the two can only be disjoint along one of the box's
+    // axes or one of the rectangle's
+    pub(crate) fn intersects_box<T: AsPrimitive<f64>>(&self, b: &[T]) -> bool {
+        let (min_x, min_y, max_x, max_y) = (b[0].as_(), b[1].as_(), b[2].as_(), b[3].as_());
+
+        let bbox = self.bbox();
+        if bbox[0] > max_x || bbox[1] > max_y || bbox[2] < min_x || bbox[3] < min_y {
+            return false;
+        }
+
+        let (cx, cy) = ((min_x + max_x) / 2., (min_y + max_y) / 2.);
+        let (hx, hy) = ((max_x - min_x) / 2., (max_y - min_y) / 2.);
+        self.axes.iter().zip(self.half_size.iter()).all(|(a, h)| {
+            // distance between the centers along the axis, against the sum of the half-widths
+            let dist = ((cx - self.center[0]) * a[0] + (cy - self.center[1]) * a[1]).abs();
+            dist <= h + hx * a[0].abs() + hy * a[1].abs()
+        })
+    }
+}
@@ -0,0 +1,52 @@
+use crate::batch::batch;
+#[cfg(feature = "rayon")]
+use crate::batch::par_batch;
+use crate::kdbush::{AllowedNumber, KDBush};
+use crate::BatchResults;
+
+impl<T: AllowedNumber> KDBush<T> {
+    /// Run `search_range` for each `[min_x, min_y, max_x, max_y]` query.
+    pub fn search_range_batch(&self, queries: &[[T; 4]]) -> BatchResults {
+        batch(queries, |q, scratch| {
+            self.search_range_with(q[0], q[1], q[2], q[3], scratch);
+        })
+    }
+
+    /// Run `search_within` for each `([qx, qy], r)` query.
+    pub fn search_within_batch(&self, queries: &[([T; 2], T)]) -> BatchResults {
+        batch(queries, |(q, r), scratch| {
+            self.search_within_with(q[0], q[1], *r, scratch);
+        })
+    }
+
+    /// Run `exact` for each `[qx, qy]` query.
+    pub fn exact_batch(&self, queries: &[[T; 2]]) -> BatchResults {
+        batch(queries, |q, scratch| {
+            self.exact_with(q[0], q[1], scratch);
+        })
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T: AllowedNumber + Send + Sync> KDBush<T> {
+    /// Like `search_range_batch`, but spreads the queries across the rayon thread pool.
+    pub fn par_search_range_batch(&self, queries: &[[T; 4]]) -> BatchResults {
+        par_batch(queries, |q, scratch| {
+            self.search_range_with(q[0], q[1], q[2], q[3], scratch);
+        })
+    }
+
+    /// Like `search_within_batch`, but spreads the queries across the rayon thread pool.
+    pub fn par_search_within_batch(&self, queries: &[([T; 2], T)]) -> BatchResults {
+        par_batch(queries, |(q, r), scratch| {
+            self.search_within_with(q[0], q[1], *r, scratch);
+        })
+    }
+
+    /// Like `exact_batch`, but spreads the queries across the rayon thread pool.
+    pub fn par_exact_batch(&self, queries: &[[T; 2]]) -> BatchResults {
+        par_batch(queries, |q, scratch| {
+            self.exact_with(q[0], q[1], scratch);
+        })
+    }
+}
@@ -0,0 +1,338 @@
+use crate::kdbush::{sort, AllowedNumber, KDBush};
+use crate::util::{BitSet, IndexVec};
+
+use core::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+// kd-sorts the live points of an index by fixing up its existing layout rather than starting
+// over. Every old subtree is already partitioned around its middle point, so the live points of
+// its two halves only end up on the wrong side of a new split when deletions have made the halves
+// uneven; each new subtree just moves the few most extreme points across to even them out, and
+// finds those by searching the old subtree's extents. Points that get moved ("strays") are handed
+// down alongside the old subtree the rest of the new subtree's points come from.
+// subtrees with fewer leaves than this are cheaper to kd-sort again, since their points are still
+// close to being in order
+const MIN_FIX_UP_LEAVES: usize = 16;
+
+pub(super) struct Compactor<'a, T: AllowedNumber> {
+    old: &'a KDBush<T>,
+    // old positions of deleted points, and of ones that have been placed, or moved across a
+    // split and handed down as strays
+    taken: BitSet,
+    // Fenwick tree counting the live points at old positions that haven't been taken
+    counts: Vec<u32>,
+    coords: Vec<T>,
+    ids: IndexVec,
+    extents: Vec<T>,
+}
+
+// a subtree of the old index
+#[derive(Clone, Copy)]
+struct Subtree {
+    node: usize,
+    left: usize,
+    right: usize,
+}
+
+impl Subtree {
+    fn halves(&self) -> (Subtree, usize, Subtree) {
+        let m = (self.left + self.right) >> 1;
+        let lower = Subtree { node: 2 * self.node + 1, left: self.left, right: m - 1 };
+        let upper = Subtree { node: 2 * self.node + 2, left: m + 1, right: self.right };
+        (lower, m, upper)
+    }
+}
+
+impl<'a, T: AllowedNumber> Compactor<'a, T> {
+    pub fn new(old: &'a KDBush<T>, num_live: usize) -> Compactor<'a, T> {
+        let n = old.ids.len();
+        let mut taken = BitSet::default();
+        let mut counts = vec![0; n + 1];
+        for i in 1..=n {
+            if old.is_live(i - 1) {
+                counts[i] += 1;
+            } else {
+                taken.insert(i - 1);
+            }
+            let parent = i + (i & i.wrapping_neg());
+            if parent <= n {
+                counts[parent] += counts[i];
+            }
+        }
+
+        Compactor {
+            old,
+            taken,
+            counts,
+            coords: vec![old.coords[0]; 2 * num_live],
+            ids: match old.ids {
+                IndexVec::U16(_) => IndexVec::U16(vec![0; num_live]),
+                IndexVec::U32(_) => IndexVec::U32(vec![0; num_live]),
+            },
+            extents: Vec::new(),
+        }
+    }
+
+    pub fn finish(mut self) -> (IndexVec, Vec<T>, Vec<T>) {
+        let root = Subtree { node: 0, left: 0, right: self.old.ids.len() - 1 };
+        self.build(0, 0, self.ids.len() - 1, 0, root, Vec::new());
+        (self.ids, self.coords, self.extents)
+    }
+
+    // fill the new subtree `node`, covering positions `left..=right`, with the remaining points
+    // of the old subtree `old` plus `strays`
+    #[allow(clippy::too_many_arguments)]
+    fn build(
+        &mut self,
+        node: usize,
+        left: usize,
+        right: usize,
+        axis: usize,
+        old: Subtree,
+        strays: Vec<usize>,
+    ) {
+        let node_size = self.old.node_size;
+        if right - left <= MIN_FIX_UP_LEAVES * node_size || old.right - old.left <= node_size {
+            // small enough to sort again, or there's no old split left to reuse
+            self.gather(old, strays, left, right, node, axis);
+            return;
+        }
+
+        let (lower, pm, upper) = old.halves();
+        let pivot = self.old.coords[2 * pm + axis];
+        let (mut below, mut above): (Vec<usize>, Vec<usize>) =
+            strays.into_iter().partition(|&i| self.old.coords[2 * i + axis] <= pivot);
+
+        // the number of points that belong below the new middle point, and how many have to
+        // move across the old split to get there
+        let m = (left + right) >> 1;
+        let k = m - left;
+        let num_below = self.count(lower) + below.len();
+        let to_move = if num_below > k { num_below - k } else { k - num_below + 1 };
+        if to_move * to_move > right - left + 1 {
+            // too lopsided to be worth fixing up, so sort this subtree from scratch
+            below.extend(above);
+            self.gather(old, below, left, right, node, axis);
+            return;
+        }
+
+        if self.is_member(pm) {
+            self.take(pm);
+            above.push(pm);
+        }
+
+        // the last point moved is the new middle one; the rest end up on the other side of it
+        let median = if num_below > k {
+            let mut moved = self.extract(lower, &mut below, axis, to_move, true);
+            let median = moved.pop().unwrap();
+            above.extend(moved);
+            median
+        } else {
+            let mut moved = self.extract(upper, &mut above, axis, to_move, false);
+            let median = moved.pop().unwrap();
+            below.extend(moved);
+            median
+        };
+        self.place(median, m);
+
+        if self.extents.len() < 4 * (node + 1) {
+            self.extents.resize(4 * (node + 1), self.coords[0]);
+        }
+        self.build(2 * node + 1, left, m - 1, 1 - axis, lower, below);
+        self.build(2 * node + 2, m + 1, right, 1 - axis, upper, above);
+
+        // the subtree covers the middle point plus both halves
+        let coords = &self.coords;
+        let mut extent = [coords[2 * m], coords[2 * m + 1], coords[2 * m], coords[2 * m + 1]];
+        for child in &[2 * node + 1, 2 * node + 2] {
+            let pos = 4 * child;
+            sort::extend(&mut extent, self.extents[pos], self.extents[pos + 1]);
+            sort::extend(&mut extent, self.extents[pos + 2], self.extents[pos + 3]);
+        }
+        self.extents[(4 * node)..(4 * node + 4)].copy_from_slice(&extent);
+    }
+
+    // copy the remaining points of `old` and the strays into `left..=right`, and kd-sort them
+    fn gather(
+        &mut self,
+        old: Subtree,
+        strays: Vec<usize>,
+        left: usize,
+        right: usize,
+        node: usize,
+        axis: usize,
+    ) {
+        let mut pos = left;
+        for i in old.left..=old.right {
+            if self.is_member(i) {
+                self.place(i, pos);
+                pos += 1;
+            }
+        }
+        for i in strays {
+            self.place(i, pos);
+            pos += 1;
+        }
+        debug_assert_eq!(pos, right + 1);
+
+        let node_size = self.old.node_size;
+        sort::sort_kd(
+            &mut self.ids,
+            &mut self.coords,
+            &mut self.extents,
+            node_size,
+            node,
+            left,
+            right,
+            axis,
+        );
+    }
+
+    // take the `num` points with the largest (or smallest) coordinates on `axis` out of the
+    // remaining points of `old` and the strays, most extreme first, with a best-first search over
+    // the old subtree's extents
+    fn extract(
+        &mut self,
+        old: Subtree,
+        strays: &mut Vec<usize>,
+        axis: usize,
+        num: usize,
+        largest: bool,
+    ) -> Vec<usize> {
+        let mut queue = BinaryHeap::new();
+        let e = &self.old.extents[(4 * old.node)..(4 * old.node + 4)];
+        let bound = if largest { e[2 + axis] } else { e[axis] };
+        queue.push(Extreme { key: bound, largest, kind: Kind::Subtree(old) });
+        for (j, &i) in strays.iter().enumerate() {
+            let key = self.old.coords[2 * i + axis];
+            queue.push(Extreme { key, largest, kind: Kind::Stray(j) });
+        }
+
+        let mut moved = Vec::with_capacity(num);
+        let mut moved_strays = Vec::new();
+        while moved.len() < num {
+            let entry = queue.pop().expect("not enough points to move across the split");
+            let sub = match entry.kind {
+                Kind::Point(i) => {
+                    self.take(i);
+                    moved.push(i);
+                    continue;
+                }
+                Kind::Stray(j) => {
+                    moved.push(strays[j]);
+                    moved_strays.push(j);
+                    continue;
+                }
+                Kind::Subtree(sub) => sub,
+            };
+
+            let mut push_point = |i: usize| {
+                let key = self.old.coords[2 * i + axis];
+                queue.push(Extreme { key, largest, kind: Kind::Point(i) });
+            };
+            if sub.right - sub.left <= self.old.node_size {
+                for i in sub.left..=sub.right {
+                    if self.is_member(i) {
+                        push_point(i);
+                    }
+                }
+                continue;
+            }
+
+            let (lower, m, upper) = sub.halves();
+            if self.is_member(m) {
+                push_point(m);
+            }
+            for half in [lower, upper].iter() {
+                if self.count(*half) > 0 {
+                    let e = &self.old.extents[(4 * half.node)..(4 * half.node + 4)];
+                    let key = if largest { e[2 + axis] } else { e[axis] };
+                    queue.push(Extreme { key, largest, kind: Kind::Subtree(*half) });
+                }
+            }
+        }
+
+        moved_strays.sort_unstable();
+        for j in moved_strays.into_iter().rev() {
+            strays.swap_remove(j);
+        }
+        moved
+    }
+
+    #[inline(always)]
+    fn place(&mut self, i: usize, pos: usize) {
+        self.coords[2 * pos] = self.old.coords[2 * i];
+        self.coords[2 * pos + 1] = self.old.coords[2 * i + 1];
+        self.ids.set(pos, self.old.ids.get(i));
+    }
+
+    #[inline(always)]
+    fn is_member(&self, i: usize) -> bool {
+        !self.taken.contains(i)
+    }
+
+    fn take(&mut self, i: usize) {
+        self.taken.insert(i);
+        let mut j = i + 1;
+        while j < self.counts.len() {
+            self.counts[j] -= 1;
+            j += j & j.wrapping_neg();
+        }
+    }
+
+    // the number of remaining points in `old`
+    fn count(&self, old: Subtree) -> usize {
+        self.prefix(old.right + 1) - self.prefix(old.left)
+    }
+
+    fn prefix(&self, mut end: usize) -> usize {
+        let mut sum = 0;
+        while end > 0 {
+            sum += self.counts[end] as usize;
+            end -= end & end.wrapping_neg();
+        }
+        sum
+    }
+}
+
+// an entry in the search for the most extreme points along an axis, keyed by the point's
+// coordinate or the far edge of the subtree's extent
+struct Extreme<T> {
+    key: T,
+    largest: bool,
+    kind: Kind,
+}
+
+enum Kind {
+    Subtree(Subtree),
+    // an old position
+    Point(usize),
+    // an index into the strays
+    Stray(usize),
+}
+
+impl<T: AllowedNumber> PartialEq for Extreme<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: AllowedNumber> Eq for Extreme<T> {}
+
+impl<T: AllowedNumber> PartialOrd for Extreme<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: AllowedNumber> Ord for Extreme<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // the max-heap pops the largest key first, or the smallest one if reversed
+        let ord = self.key.partial_cmp(&other.key).unwrap_or(Ordering::Equal);
+        if self.largest {
+            ord
+        } else {
+            ord.reverse()
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::geometry::{project, segment_box_sq_dist, sq_dist};
+use crate::kdbush::{AllowedNumber, KDBush};
+
+use num_traits::AsPrimitive;
+
+impl<T: AllowedNumber + AsPrimitive<f64>> KDBush<T> {
+    /// Search for points within distance `d` of the polyline through the given points, e.g. for
+    /// everything along a route. Each point is yielded once, as `(id, distance, segment)`, where
+    /// `segment` is the index of the nearest segment (the one from `line[segment]` to
+    /// `line[segment + 1]`). Subtrees are pruned by their distance to the polyline, and only
+    /// the segments close enough to a subtree are checked against the points inside it.
+    pub fn search_corridor<'a>(
+        &'a self,
+        line: &[[T; 2]],
+        d: T,
+    ) -> impl Iterator<Item = (usize, f64, usize)> + 'a {
+        let d2 = d.as_() * d.as_();
+        let mut segments: Vec<[f64; 4]> = line
+            .windows(2)
+            .map(|w| [w[0][0].as_(), w[0][1].as_(), w[1][0].as_(), w[1][1].as_()])
+            .collect();
+        if let [p] = line {
+            // a single point is a segment of length zero
+            segments.push([p[0].as_(), p[1].as_(), p[0].as_(), p[1].as_()]);
+        }
+
+        // each subtree carries the segments that are close enough to it, so that the points inside
+        // it only get checked against those
+        let all: Vec<usize> = (0..segments.len()).collect();
+        let near_segments = segments.clone();
+
+        self.search_extents_with_state(
+            all,
+            move |e, candidates| {
+                let extent: [f64; 4] = [e[0].as_(), e[1].as_(), e[2].as_(), e[3].as_()];
+                let near: Vec<usize> = candidates
+                    .iter()
+                    .cloned()
+                    .filter(|&s| segment_box_sq_dist(near_segments[s], &extent) <= d2)
+                    .collect();
+                if near.is_empty() {
+                    None
+                } else {
+                    Some(near)
+                }
+            },
+            move |x, y, near| {
+                // the nearest of the remaining segments to the point, if it's in range
+                let p = [x.as_(), y.as_()];
+                let (dist, s) = near
+                    .iter()
+                    .map(|&s| (sq_dist(p, project(p, segments[s]).0), s))
+                    .fold((f64::INFINITY, 0), |a, b| if b.0 < a.0 { b } else { a });
+                if dist <= d2 {
+                    Some((dist.sqrt(), s))
+                } else {
+                    None
+                }
+            },
+        )
+        .map(|(id, (dist, s))| (id, dist, s))
+    }
+}
@@ -0,0 +1,45 @@
+use crate::kdbush::{AllowedNumber, KDBush};
+
+impl<T: AllowedNumber> KDBush<T> {
+    /// Count the points in the given range without enumerating them. Subtrees that lie entirely
+    /// inside the range are counted wholesale, so only the edges of the range need to be scanned.
+    pub fn count_range(&self, min_x: T, min_y: T, max_x: T, max_y: T) -> usize {
+        self.count_range_node([min_x, min_y, max_x, max_y], 0, 0, self.ids.len() - 1)
+    }
+
+    fn count_range_node(&self, query: [T; 4], node: usize, left: usize, right: usize) -> usize {
+        let [min_x, min_y, max_x, max_y] = query;
+        let extent = &self.extents[(4 * node)..(4 * node + 4)];
+
+        // skip subtrees that miss the query entirely
+        if extent[0] > max_x || extent[1] > max_y || extent[2] < min_x || extent[3] < min_y {
+            return 0;
+        }
+        // and count subtrees that are entirely inside it without visiting their points (or at
+        // least without checking their coordinates, if anything's been deleted)
+        if extent[0] >= min_x && extent[1] >= min_y && extent[2] <= max_x && extent[3] <= max_y {
+            if self.deleted.is_empty() {
+                return right - left + 1;
+            }
+            return (left..=right).filter(|i| self.is_live(*i)).count();
+        }
+
+        let in_range = |i: usize| {
+            let x = self.coords[2 * i];
+            let y = self.coords[2 * i + 1];
+            x >= min_x && x <= max_x && y >= min_y && y <= max_y && self.is_live(i)
+        };
+
+        // if we reached "tree node", search linearly
+        if right - left <= self.node_size {
+            return (left..=right).filter(|i| in_range(*i)).count();
+        }
+
+        // otherwise count the middle item and both halves
+        let m = (left + right) >> 1;
+
+        in_range(m) as usize
+            + self.count_range_node(query, 2 * node + 1, left, m - 1)
+            + self.count_range_node(query, 2 * node + 2, m + 1, right)
+    }
+}
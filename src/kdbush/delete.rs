@@ -0,0 +1,65 @@
+use crate::kdbush::{compact::Compactor, AllowedNumber, KDBush};
+use crate::util::BitSet;
+
+impl<T: AllowedNumber> KDBush<T> {
+    /// Mark the point with the given id as deleted, so that no query returns it. Returns whether
+    /// the point was newly deleted; ids that aren't in the index (including ones left out by an
+    /// earlier `compact`) are ignored. Takes constant time, unless the index came from `compact`
+    /// and was built without `set_id_lookup(true)`, in which case it takes linear time.
+    pub fn delete(&mut self, id: usize) -> bool {
+        if self.deleted.contains(id) || !self.has_id(id) {
+            return false;
+        }
+        self.deleted.insert(id)
+    }
+
+    pub fn is_deleted(&self, id: usize) -> bool {
+        self.deleted.contains(id)
+    }
+
+    /// The number of points in the index, including deleted ones.
+    pub fn num_items(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn num_deleted(&self) -> usize {
+        self.deleted.len()
+    }
+
+    /// The fraction of the index's points that have been deleted, to help decide when it's worth
+    /// calling `compact`.
+    pub fn deleted_ratio(&self) -> f64 {
+        self.num_deleted() as f64 / self.num_items() as f64
+    }
+
+    /// Build a new index containing only the points that haven't been deleted. Points keep their
+    /// original ids. The existing kd order is reused: deleting points leaves each subtree's halves
+    /// on the right sides of its split, so only the few points needed to even up their sizes get
+    /// moved. With scattered deletions this takes well under half the time of building a new
+    /// index from the remaining points.
+    ///
+    /// Panics if every point has been deleted.
+    pub fn compact(&self) -> KDBush<T> {
+        let num_live = self.ids.len() - self.deleted.len();
+        assert!(num_live > 0, "can't compact an index whose points have all been deleted");
+
+        let (ids, coords, extents) = Compactor::new(self, num_live).finish();
+        let positions = self.positions.as_ref().map(|_| ids.invert(num_live));
+
+        KDBush {
+            node_size: self.node_size,
+            coords,
+            ids,
+            extents,
+            deleted: BitSet::default(),
+            positions,
+            compacted: true,
+        }
+    }
+
+    // whether the point at the given kd-sorted position hasn't been deleted
+    #[inline(always)]
+    pub(super) fn is_live(&self, i: usize) -> bool {
+        self.deleted.is_empty() || !self.deleted.contains(self.ids.get(i) as usize)
+    }
+}
@@ -0,0 +1,245 @@
+use crate::batch::batch;
+#[cfg(feature = "rayon")]
+use crate::batch::par_batch;
+use crate::dynamic::{LogStructured, StaticIndex, DEFAULT_BUFFER_SIZE};
+use crate::kdbush::{within::sq_dist, AllowedNumber, KDBush, KDBushBuilder, DEFAULT_NODE_SIZE};
+use crate::{BatchResults, QueryScratch};
+
+use core::ops::ControlFlow;
+
+impl<T: AllowedNumber> StaticIndex for KDBush<T> {
+    type Item = [T; 2];
+
+    fn build<I: Iterator<Item = [T; 2]>>(points: I, node_size: usize) -> KDBush<T> {
+        let mut builder = KDBushBuilder::new_with_node_size(node_size);
+        builder.extend(points);
+        builder.finish()
+    }
+
+    fn for_each_item<F: FnMut(usize, [T; 2])>(&self, mut f: F) {
+        for i in 0..self.ids.len() {
+            f(self.ids.get(i) as usize, [self.coords[2 * i], self.coords[2 * i + 1]]);
+        }
+    }
+}
+
+/// A `KDBush` that supports inserting and removing points, by keeping a small buffer of recent
+/// insertions plus a logarithmic series of static indexes that get merged as they fill up.
+#[derive(Clone)]
+pub struct DynamicKDBush<T: AllowedNumber> {
+    inner: LogStructured<KDBush<T>>,
+}
+
+impl<T: AllowedNumber> DynamicKDBush<T> {
+    #[inline(always)]
+    pub fn new() -> DynamicKDBush<T> {
+        DynamicKDBush::new_with_node_size(DEFAULT_NODE_SIZE)
+    }
+
+    pub fn new_with_node_size(node_size: usize) -> DynamicKDBush<T> {
+        DynamicKDBush::new_with_buffer_size(node_size, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Like `new_with_node_size`, but also sets how many insertions are buffered before they get
+    /// indexed. Smaller buffers make queries cheaper and insertions more expensive.
+    pub fn new_with_buffer_size(node_size: usize, buffer_size: usize) -> DynamicKDBush<T> {
+        DynamicKDBush { inner: LogStructured::new(node_size, buffer_size) }
+    }
+
+    /// Add a point, returning its id. Ids are assigned sequentially and never reused.
+    pub fn insert<U: std::borrow::Borrow<[T; 2]>>(&mut self, point: U) -> usize {
+        self.inner.insert(*point.borrow())
+    }
+
+    /// Remove the point with the given id, returning whether it was present.
+    pub fn remove(&mut self, id: usize) -> bool {
+        self.inner.remove(id)
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.inner.contains(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn search_range<'a>(
+        &'a self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.inner.query(
+            move |p| in_range(p, min_x, min_y, max_x, max_y),
+            move |bush| bush.search_range(min_x, min_y, max_x, max_y),
+        )
+    }
+
+    pub fn visit_range<B, F: FnMut(usize) -> ControlFlow<B>>(
+        &self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+        visitor: F,
+    ) -> ControlFlow<B> {
+        self.inner.visit(
+            |p| in_range(p, min_x, min_y, max_x, max_y),
+            |bush, f| bush.visit_range(min_x, min_y, max_x, max_y, f),
+            visitor,
+        )
+    }
+
+    pub fn search_range_with<'s>(
+        &self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+        scratch: &'s mut QueryScratch,
+    ) -> &'s [usize] {
+        self.inner.visit_into(
+            scratch,
+            |p| in_range(p, min_x, min_y, max_x, max_y),
+            |bush, f| bush.visit_range(min_x, min_y, max_x, max_y, f),
+        )
+    }
+
+    pub fn count_range(&self, min_x: T, min_y: T, max_x: T, max_y: T) -> usize {
+        self.inner.count(
+            |p| in_range(p, min_x, min_y, max_x, max_y),
+            |bush| bush.count_range(min_x, min_y, max_x, max_y),
+            |bush| bush.search_range(min_x, min_y, max_x, max_y),
+        )
+    }
+
+    pub fn search_within<'a>(&'a self, qx: T, qy: T, r: T) -> impl Iterator<Item = usize> + 'a {
+        let r2 = r * r;
+        self.inner.query(
+            move |p| sq_dist(p[0], p[1], qx, qy) <= r2,
+            move |bush| bush.search_within(qx, qy, r),
+        )
+    }
+
+    pub fn visit_within<B, F: FnMut(usize) -> ControlFlow<B>>(
+        &self,
+        qx: T,
+        qy: T,
+        r: T,
+        visitor: F,
+    ) -> ControlFlow<B> {
+        let r2 = r * r;
+        self.inner.visit(
+            |p| sq_dist(p[0], p[1], qx, qy) <= r2,
+            |bush, f| bush.visit_within(qx, qy, r, f),
+            visitor,
+        )
+    }
+
+    pub fn search_within_with<'s>(
+        &self,
+        qx: T,
+        qy: T,
+        r: T,
+        scratch: &'s mut QueryScratch,
+    ) -> &'s [usize] {
+        let r2 = r * r;
+        self.inner.visit_into(
+            scratch,
+            |p| sq_dist(p[0], p[1], qx, qy) <= r2,
+            |bush, f| bush.visit_within(qx, qy, r, f),
+        )
+    }
+
+    pub fn exact<'a>(&'a self, qx: T, qy: T) -> impl Iterator<Item = usize> + 'a {
+        self.inner.query(move |p| p[0] == qx && p[1] == qy, move |bush| bush.exact(qx, qy))
+    }
+
+    pub fn visit_exact<B, F: FnMut(usize) -> ControlFlow<B>>(
+        &self,
+        qx: T,
+        qy: T,
+        visitor: F,
+    ) -> ControlFlow<B> {
+        self.inner.visit(
+            |p| p[0] == qx && p[1] == qy,
+            |bush, f| bush.visit_exact(qx, qy, f),
+            visitor,
+        )
+    }
+
+    pub fn exact_with<'s>(&self, qx: T, qy: T, scratch: &'s mut QueryScratch) -> &'s [usize] {
+        self.inner.visit_into(
+            scratch,
+            |p| p[0] == qx && p[1] == qy,
+            |bush, f| bush.visit_exact(qx, qy, f),
+        )
+    }
+
+    /// Run `search_range` for each `[min_x, min_y, max_x, max_y]` query.
+    pub fn search_range_batch(&self, queries: &[[T; 4]]) -> BatchResults {
+        batch(queries, |q, scratch| {
+            self.search_range_with(q[0], q[1], q[2], q[3], scratch);
+        })
+    }
+
+    /// Run `search_within` for each `([qx, qy], r)` query.
+    pub fn search_within_batch(&self, queries: &[([T; 2], T)]) -> BatchResults {
+        batch(queries, |(q, r), scratch| {
+            self.search_within_with(q[0], q[1], *r, scratch);
+        })
+    }
+
+    /// Run `exact` for each `[qx, qy]` query.
+    pub fn exact_batch(&self, queries: &[[T; 2]]) -> BatchResults {
+        batch(queries, |q, scratch| {
+            self.exact_with(q[0], q[1], scratch);
+        })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn num_stored(&self) -> usize {
+        self.inner.num_stored()
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T: AllowedNumber + Send + Sync> DynamicKDBush<T> {
+    /// Like `search_range_batch`, but spreads the queries across the rayon thread pool.
+    pub fn par_search_range_batch(&self, queries: &[[T; 4]]) -> BatchResults {
+        par_batch(queries, |q, scratch| {
+            self.search_range_with(q[0], q[1], q[2], q[3], scratch);
+        })
+    }
+
+    /// Like `search_within_batch`, but spreads the queries across the rayon thread pool.
+    pub fn par_search_within_batch(&self, queries: &[([T; 2], T)]) -> BatchResults {
+        par_batch(queries, |(q, r), scratch| {
+            self.search_within_with(q[0], q[1], *r, scratch);
+        })
+    }
+
+    /// Like `exact_batch`, but spreads the queries across the rayon thread pool.
+    pub fn par_exact_batch(&self, queries: &[[T; 2]]) -> BatchResults {
+        par_batch(queries, |q, scratch| {
+            self.exact_with(q[0], q[1], scratch);
+        })
+    }
+}
+
+#[inline(always)]
+fn in_range<T: AllowedNumber>(p: &[T; 2], min_x: T, min_y: T, max_x: T, max_y: T) -> bool {
+    p[0] >= min_x && p[0] <= max_x && p[1] >= min_y && p[1] <= max_y
+}
+
+impl<T: AllowedNumber> Default for DynamicKDBush<T> {
+    fn default() -> Self {
+        DynamicKDBush::new()
+    }
+}
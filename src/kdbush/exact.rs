@@ -1,8 +1,12 @@
 use crate::kdbush::{AllowedNumber, KDBush};
+use crate::QueryScratch;
+
+use core::ops::ControlFlow;
 
 use genawaiter::rc::Gen;
 
 impl<T: AllowedNumber> KDBush<T> {
+    #[allow(clippy::len_zero)]
     pub fn exact<'a>(&'a self, qx: T, qy: T) -> impl Iterator<Item = usize> + 'a {
         let mut stack = vec![0, self.ids.len() - 1, 0];
 
@@ -18,7 +22,10 @@ impl<T: AllowedNumber> KDBush<T> {
                 // if we reached "tree node", search linearly
                 if right - left <= self.node_size {
                     for i in left..=right {
-                        if self.coords[2 * i] == qx && self.coords[2 * i + 1] == qy {
+                        if self.coords[2 * i] == qx
+                            && self.coords[2 * i + 1] == qy
+                            && self.is_live(i)
+                        {
                             co.yield_(self.ids.get(i) as usize).await;
                         }
                     }
@@ -31,7 +38,7 @@ impl<T: AllowedNumber> KDBush<T> {
                 // include the middle item if it's in range
                 let x = self.coords[2 * m];
                 let y = self.coords[2 * m + 1];
-                if x == qx && y == qy {
+                if x == qx && y == qy && self.is_live(m) {
                     co.yield_(self.ids.get(m) as usize).await;
                 }
 
@@ -54,6 +61,7 @@ impl<T: AllowedNumber> KDBush<T> {
         .into_iter()
     }
 
+    #[allow(clippy::len_zero)]
     pub fn exact_as_vec(&self, qx: T, qy: T) -> Vec<usize> {
         let mut stack = vec![0, self.ids.len() - 1, 0];
         let mut results: Vec<usize> = Vec::new();
@@ -69,7 +77,7 @@ impl<T: AllowedNumber> KDBush<T> {
             // if we reached "tree node", search linearly
             if right - left <= self.node_size {
                 for i in left..=right {
-                    if self.coords[2 * i] == qx && self.coords[2 * i + 1] == qy {
+                    if self.coords[2 * i] == qx && self.coords[2 * i + 1] == qy && self.is_live(i) {
                         results.push(self.ids.get(i) as usize);
                     }
                 }
@@ -82,7 +90,7 @@ impl<T: AllowedNumber> KDBush<T> {
             // include the middle item if it's in range
             let x = self.coords[2 * m];
             let y = self.coords[2 * m + 1];
-            if x == qx && y == qy {
+            if x == qx && y == qy && self.is_live(m) {
                 results.push(self.ids.get(m) as usize);
             }
 
@@ -104,4 +112,28 @@ impl<T: AllowedNumber> KDBush<T> {
 
         results
     }
+
+    pub fn visit_exact<B, F: FnMut(usize) -> ControlFlow<B>>(
+        &self,
+        qx: T,
+        qy: T,
+        mut visitor: F,
+    ) -> ControlFlow<B> {
+        self.walk(
+            0,
+            self.ids.len() - 1,
+            0,
+            &|x, y| x == qx && y == qy,
+            &|axis, x, y| if axis == 0 { (qx <= x, qx >= x) } else { (qy <= y, qy >= y) },
+            &mut visitor,
+        )
+    }
+
+    pub fn exact_with<'s>(&self, qx: T, qy: T, scratch: &'s mut QueryScratch) -> &'s [usize] {
+        self.search_into(
+            scratch,
+            |x, y| x == qx && y == qy,
+            |axis, x, y| if axis == 0 { (qx <= x, qx >= x) } else { (qy <= y, qy >= y) },
+        )
+    }
 }
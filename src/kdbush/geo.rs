@@ -0,0 +1,22 @@
+use crate::kdbush::KDBush;
+
+impl KDBush<f64> {
+    /// Like `search_range`, but for points stored as `[lon, lat]`: if `min_lon > max_lon`, the
+    /// range is taken to cross the antimeridian, and both sides of it are searched.
+    pub fn geo_search_range<'a>(
+        &'a self,
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let wrapped = min_lon > max_lon;
+
+        let east =
+            self.search_range(min_lon, min_lat, if wrapped { 180. } else { max_lon }, max_lat);
+        let west =
+            if wrapped { Some(self.search_range(-180., min_lat, max_lon, max_lat)) } else { None };
+
+        east.chain(west.into_iter().flatten())
+    }
+}
@@ -0,0 +1,41 @@
+use crate::kdbush::{AllowedNumber, KDBush};
+
+impl<T: AllowedNumber> KDBush<T> {
+    /// The coordinates of the point with the given id, or `None` if there's no such point or it's
+    /// been deleted. Takes constant time if the index was built with `set_id_lookup(true)`, and
+    /// linear time otherwise.
+    pub fn point(&self, id: usize) -> Option<[T; 2]> {
+        if self.deleted.contains(id) {
+            return None;
+        }
+
+        let i = self.position_of(id)?;
+        Some([self.coords[2 * i], self.coords[2 * i + 1]])
+    }
+
+    /// All the points in the index that haven't been deleted, as `(id, [x, y])` pairs in kd-sorted
+    /// order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, [T; 2])> + 'a {
+        (0..self.ids.len())
+            .filter(move |i| self.is_live(*i))
+            .map(move |i| (self.ids.get(i) as usize, [self.coords[2 * i], self.coords[2 * i + 1]]))
+    }
+
+    // whether the index has a point (deleted or not) with the given id; freshly built indexes
+    // hold every id below their number of points, so only compacted ones need a search
+    pub(super) fn has_id(&self, id: usize) -> bool {
+        match &self.positions {
+            Some(positions) => positions.position(id).is_some(),
+            None if !self.compacted => id < self.ids.len(),
+            None => self.position_of(id).is_some(),
+        }
+    }
+
+    // the kd-sorted position of the point with the given id
+    pub(super) fn position_of(&self, id: usize) -> Option<usize> {
+        match &self.positions {
+            Some(positions) => positions.position(id),
+            None => (0..self.ids.len()).find(|i| self.ids.get(*i) as usize == id),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::kdbush::AllowedNumber;
+
+/// A way of measuring the distance between points, for the `*_within_metric` queries.
+///
+/// Distances only need to be comparable with each other, so a metric can work in whatever units
+/// are cheapest, as long as `radius` converts a query radius into the same units (`Euclidean`,
+/// for example, compares squared distances to avoid taking square roots).
+pub trait Metric<T: AllowedNumber> {
+    /// The distance between two points that are `dx` apart along the x axis and `dy` apart along
+    /// the y axis (both non-negative).
+    fn distance(&self, dx: T, dy: T) -> T;
+
+    /// A lower bound on the distance to any point on the far side of a split line that's `d` away
+    /// along the given axis (0 for x, 1 for y).
+    fn split_distance(&self, axis: usize, d: T) -> T;
+
+    /// Convert a query radius into the units `distance` returns.
+    fn radius(&self, r: T) -> T {
+        r
+    }
+}
+
+/// The L1 or taxicab distance, `dx + dy`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Manhattan;
+
+/// The usual straight-line (L2) distance.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Euclidean;
+
+/// The L∞ or chessboard distance, `max(dx, dy)`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Chebyshev;
+
+/// Euclidean distance with each axis scaled by its own weight first, so that the points within
+/// a radius form an axis-aligned ellipse rather than a circle.
+#[derive(Clone, Copy, Debug)]
+pub struct WeightedEuclidean<T> {
+    pub weights: [T; 2],
+}
+
+impl<T: AllowedNumber> Metric<T> for Manhattan {
+    fn distance(&self, dx: T, dy: T) -> T {
+        dx + dy
+    }
+
+    fn split_distance(&self, _axis: usize, d: T) -> T {
+        d
+    }
+}
+
+impl<T: AllowedNumber> Metric<T> for Euclidean {
+    fn distance(&self, dx: T, dy: T) -> T {
+        dx * dx + dy * dy
+    }
+
+    fn split_distance(&self, _axis: usize, d: T) -> T {
+        d * d
+    }
+
+    fn radius(&self, r: T) -> T {
+        r * r
+    }
+}
+
+impl<T: AllowedNumber> Metric<T> for Chebyshev {
+    fn distance(&self, dx: T, dy: T) -> T {
+        if dx > dy {
+            dx
+        } else {
+            dy
+        }
+    }
+
+    fn split_distance(&self, _axis: usize, d: T) -> T {
+        d
+    }
+}
+
+impl<T: AllowedNumber> Metric<T> for WeightedEuclidean<T> {
+    fn distance(&self, dx: T, dy: T) -> T {
+        let (dx, dy) = (dx * self.weights[0], dy * self.weights[1]);
+        dx * dx + dy * dy
+    }
+
+    fn split_distance(&self, axis: usize, d: T) -> T {
+        let d = d * self.weights[axis];
+        d * d
+    }
+
+    fn radius(&self, r: T) -> T {
+        r * r
+    }
+}
@@ -3,15 +3,33 @@ use num_traits::NumOps;
 use core::borrow::Borrow;
 use core::iter::FromIterator;
 
-use crate::util::IndexVec;
-
+use crate::util::{BitSet, IndexVec};
+
+mod batch;
+mod compact;
+mod corridor;
+mod count;
+mod delete;
+mod dynamic;
 mod exact;
+mod geo;
+mod lookup;
+mod metric;
+mod oriented;
 mod range;
+mod scratch;
+mod search;
 mod sort;
 #[cfg(test)]
 mod test;
+mod visit;
+mod weighted;
 mod within;
 
+pub use dynamic::DynamicKDBush;
+pub use metric::{Chebyshev, Euclidean, Manhattan, Metric, WeightedEuclidean};
+pub use weighted::WeightedKDBush;
+
 pub trait AllowedNumber: PartialOrd + NumOps + Copy
 where
     Self: std::marker::Sized,
@@ -25,12 +43,21 @@ pub struct KDBush<T: AllowedNumber> {
     node_size: usize,
     coords: Vec<T>,
     ids: IndexVec,
+    // bounding box of each implicit kd subtree, in the heap order used by `sort::sort_kd`
+    extents: Vec<T>,
+    // ids of deleted points
+    deleted: BitSet,
+    // kd-sorted position of each id, if the builder was asked for one
+    positions: Option<IndexVec>,
+    // whether this came from `compact`, so that its ids might not run from 0 to `num_items - 1`
+    compacted: bool,
 }
 
 #[derive(Clone)]
 pub struct KDBushBuilder<T: AllowedNumber> {
     node_size: usize,
     coords: Vec<T>,
+    id_lookup: bool,
 }
 
 pub const DEFAULT_NODE_SIZE: usize = 64;
@@ -42,7 +69,7 @@ impl<T: AllowedNumber> KDBushBuilder<T> {
     }
 
     pub fn new_with_node_size(node_size: usize) -> KDBushBuilder<T> {
-        KDBushBuilder { coords: Vec::new(), node_size }
+        KDBushBuilder { coords: Vec::new(), node_size, id_lookup: false }
     }
 
     pub fn add<U: Borrow<[T; 2]>>(&mut self, point: U) -> usize {
@@ -53,6 +80,12 @@ impl<T: AllowedNumber> KDBushBuilder<T> {
         (self.coords.len() >> 1) - 1
     }
 
+    /// Whether to build a table mapping ids to their position in the index, which makes looking
+    /// up points by id (`point`) take constant rather than linear time. Off by default.
+    pub fn set_id_lookup(&mut self, enabled: bool) {
+        self.id_lookup = enabled;
+    }
+
     pub fn finish(mut self) -> KDBush<T> {
         let num_points = self.coords.len() >> 1;
         let mut ids = if num_points < 65536 {
@@ -62,9 +95,35 @@ impl<T: AllowedNumber> KDBushBuilder<T> {
         };
 
         // kd-sort both arrays for efficient search (see comments in sort.js)
-        sort::sort_kd(&mut ids, &mut self.coords, self.node_size, 0, num_points - 1, 0);
+        let mut extents = Vec::new();
+        sort::sort_kd(
+            &mut ids,
+            &mut self.coords,
+            &mut extents,
+            self.node_size,
+            0,
+            0,
+            num_points - 1,
+            0,
+        );
+
+        let positions = if self.id_lookup { Some(ids.invert(num_points)) } else { None };
+
+        KDBush {
+            node_size: self.node_size,
+            coords: self.coords,
+            ids,
+            extents,
+            deleted: BitSet::default(),
+            positions,
+            compacted: false,
+        }
+    }
+}
 
-        KDBush { node_size: self.node_size, coords: self.coords, ids }
+impl<T: AllowedNumber> Default for KDBushBuilder<T> {
+    fn default() -> Self {
+        KDBushBuilder::new()
     }
 }
 
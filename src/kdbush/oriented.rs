@@ -0,0 +1,15 @@
+use crate::geometry::OrientedRect;
+use crate::kdbush::{AllowedNumber, KDBush};
+
+use num_traits::AsPrimitive;
+
+impl<T: AllowedNumber + AsPrimitive<f64>> KDBush<T> {
+    /// Search for points inside a rotated rectangle. Subtrees are pruned with a separating axis
+    /// test against the rectangle itself, rather than its bounding box.
+    pub fn search_oriented<'a>(&'a self, rect: OrientedRect) -> impl Iterator<Item = usize> + 'a {
+        self.search_extents(
+            move |extent| rect.intersects_box(extent),
+            move |x, y| rect.contains(x.as_(), y.as_()),
+        )
+    }
+}
@@ -1,8 +1,12 @@
 use crate::kdbush::{AllowedNumber, KDBush};
+use crate::QueryScratch;
+
+use core::ops::ControlFlow;
 
 use genawaiter::rc::Gen;
 
 impl<T: AllowedNumber> KDBush<T> {
+    #[allow(clippy::len_zero)]
     pub fn search_range<'a>(
         &'a self,
         min_x: T,
@@ -26,7 +30,7 @@ impl<T: AllowedNumber> KDBush<T> {
                     for i in left..=right {
                         let x = self.coords[2 * i];
                         let y = self.coords[2 * i + 1];
-                        if x >= min_x && x <= max_x && y >= min_y && y <= max_y {
+                        if x >= min_x && x <= max_x && y >= min_y && y <= max_y && self.is_live(i) {
                             co.yield_(self.ids.get(i) as usize).await;
                         }
                     }
@@ -39,7 +43,7 @@ impl<T: AllowedNumber> KDBush<T> {
                 // include the middle item if it's in range
                 let x = self.coords[2 * m];
                 let y = self.coords[2 * m + 1];
-                if x >= min_x && x <= max_x && y >= min_y && y <= max_y {
+                if x >= min_x && x <= max_x && y >= min_y && y <= max_y && self.is_live(m) {
                     co.yield_(self.ids.get(m) as usize).await;
                 }
 
@@ -61,4 +65,75 @@ impl<T: AllowedNumber> KDBush<T> {
         })
         .into_iter()
     }
+
+    pub fn visit_range<B, F: FnMut(usize) -> ControlFlow<B>>(
+        &self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+        mut visitor: F,
+    ) -> ControlFlow<B> {
+        self.walk(
+            0,
+            self.ids.len() - 1,
+            0,
+            &|x, y| x >= min_x && x <= max_x && y >= min_y && y <= max_y,
+            &|axis, x, y| {
+                if axis == 0 {
+                    (min_x <= x, max_x >= x)
+                } else {
+                    (min_y <= y, max_y >= y)
+                }
+            },
+            &mut visitor,
+        )
+    }
+
+    pub fn search_range_with<'s>(
+        &self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+        scratch: &'s mut QueryScratch,
+    ) -> &'s [usize] {
+        self.search_into(
+            scratch,
+            |x, y| x >= min_x && x <= max_x && y >= min_y && y <= max_y,
+            |axis, x, y| {
+                if axis == 0 {
+                    (min_x <= x, max_x >= x)
+                } else {
+                    (min_y <= y, max_y >= y)
+                }
+            },
+        )
+    }
+
+    /// Like `search_range`, but yields each point's coordinates along with its id.
+    pub fn search_range_points<'a>(
+        &'a self,
+        min_x: T,
+        min_y: T,
+        max_x: T,
+        max_y: T,
+    ) -> impl Iterator<Item = (usize, [T; 2])> + 'a {
+        self.search(
+            move |id, x, y| {
+                if x >= min_x && x <= max_x && y >= min_y && y <= max_y {
+                    Some((id, [x, y]))
+                } else {
+                    None
+                }
+            },
+            move |axis, x, y| {
+                if axis == 0 {
+                    (min_x <= x, max_x >= x)
+                } else {
+                    (min_y <= y, max_y >= y)
+                }
+            },
+        )
+    }
 }
@@ -0,0 +1,65 @@
+use crate::kdbush::{AllowedNumber, KDBush};
+use crate::QueryScratch;
+
+impl<T: AllowedNumber> KDBush<T> {
+    // stack-based search that collects into the buffers of a reusable scratch instead of
+    // allocating; `contains` and `split` work the same as in `walk`
+    pub(super) fn search_into<'s, P, S>(
+        &self,
+        scratch: &'s mut QueryScratch,
+        contains: P,
+        split: S,
+    ) -> &'s [usize]
+    where
+        P: Fn(T, T) -> bool,
+        S: Fn(usize, T, T) -> (bool, bool),
+    {
+        scratch.clear();
+        let QueryScratch { stack, results } = scratch;
+        stack.extend_from_slice(&[0, self.ids.len() - 1, 0]);
+
+        // recursively search for items in range in the kd-sorted arrays
+        while let Some(axis) = stack.pop() {
+            // we always push three at a time, so the other two pops will always work -- unwrap
+            // is safe here
+            let right = stack.pop().unwrap();
+            let left = stack.pop().unwrap();
+
+            // if we reached "tree node", search linearly
+            if right - left <= self.node_size {
+                for i in left..=right {
+                    if contains(self.coords[2 * i], self.coords[2 * i + 1]) && self.is_live(i) {
+                        results.push(self.ids.get(i) as usize);
+                    }
+                }
+                continue;
+            }
+
+            // otherwise find the middle index
+            let m = (left + right) >> 1;
+
+            // include the middle item if it's in range
+            let x = self.coords[2 * m];
+            let y = self.coords[2 * m + 1];
+            if contains(x, y) && self.is_live(m) {
+                results.push(self.ids.get(m) as usize);
+            }
+
+            // queue search in halves that intersect the query
+            let (over_min, under_max) = split(axis, x, y);
+
+            if over_min {
+                stack.push(left);
+                stack.push(m - 1);
+                stack.push(1 - axis);
+            }
+            if under_max {
+                stack.push(m + 1);
+                stack.push(right);
+                stack.push(1 - axis);
+            }
+        }
+
+        results
+    }
+}
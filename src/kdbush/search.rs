@@ -0,0 +1,143 @@
+use crate::kdbush::{AllowedNumber, KDBush};
+
+use genawaiter::rc::Gen;
+
+impl<T: AllowedNumber> KDBush<T> {
+    // lazy, stack-based search that yields whatever `hit` returns for each live point (given its
+    // id and coordinates); `split` works the same as in `walk`
+    pub(super) fn search<'a, R, H, S>(&'a self, hit: H, split: S) -> impl Iterator<Item = R> + 'a
+    where
+        R: 'a,
+        H: Fn(usize, T, T) -> Option<R> + 'a,
+        S: Fn(usize, T, T) -> (bool, bool) + 'a,
+    {
+        let mut stack = vec![0, self.ids.len() - 1, 0];
+
+        Gen::new(|co| async move {
+            // recursively search for items in range in the kd-sorted arrays
+            while let Some(axis) = stack.pop() {
+                // we always push three at a time, so the other two pops will always work -- unwrap
+                // is safe here
+                let right = stack.pop().unwrap();
+                let left = stack.pop().unwrap();
+
+                // if we reached "tree node", search linearly
+                if right - left <= self.node_size {
+                    for i in left..=right {
+                        if !self.is_live(i) {
+                            continue;
+                        }
+                        let id = self.ids.get(i) as usize;
+                        if let Some(result) = hit(id, self.coords[2 * i], self.coords[2 * i + 1]) {
+                            co.yield_(result).await;
+                        }
+                    }
+                    continue;
+                }
+
+                // otherwise find the middle index
+                let m = (left + right) >> 1;
+
+                // include the middle item if it's in range
+                let x = self.coords[2 * m];
+                let y = self.coords[2 * m + 1];
+                if self.is_live(m) {
+                    if let Some(result) = hit(self.ids.get(m) as usize, x, y) {
+                        co.yield_(result).await;
+                    }
+                }
+
+                // queue search in halves that intersect the query
+                let (over_min, under_max) = split(axis, x, y);
+
+                if over_min {
+                    stack.push(left);
+                    stack.push(m - 1);
+                    stack.push(1 - axis);
+                }
+                if under_max {
+                    stack.push(m + 1);
+                    stack.push(right);
+                    stack.push(1 - axis);
+                }
+            }
+        })
+        .into_iter()
+    }
+
+    // lazy search like `search`, but that prunes subtrees by their extents rather than by the
+    // split at their middle point: subtrees are only descended into if `overlaps` returns true for
+    // their bounding box, and live points are yielded if `contains` returns true for them
+    pub(super) fn search_extents<'a, O, P>(
+        &'a self,
+        overlaps: O,
+        contains: P,
+    ) -> impl Iterator<Item = usize> + 'a
+    where
+        O: Fn(&[T]) -> bool + 'a,
+        P: Fn(T, T) -> bool + 'a,
+    {
+        self.search_extents_with_state(
+            (),
+            move |extent, _| if overlaps(extent) { Some(()) } else { None },
+            move |x, y, _| if contains(x, y) { Some(()) } else { None },
+        )
+        .map(|(id, _)| id)
+    }
+
+    // like `search_extents`, but passes state down the tree: `descend` gets each subtree's bounding
+    // box along with its parent's state (`root` for the whole tree), and returns the subtree's own
+    // state, or `None` to prune it. Live points are yielded along with whatever `matches` returns
+    // for them and their subtree's state, unless that's `None`
+    pub(super) fn search_extents_with_state<'a, S, R, D, P>(
+        &'a self,
+        root: S,
+        descend: D,
+        matches: P,
+    ) -> impl Iterator<Item = (usize, R)> + 'a
+    where
+        S: Clone + 'a,
+        R: 'a,
+        D: Fn(&[T], &S) -> Option<S> + 'a,
+        P: Fn(T, T, &S) -> Option<R> + 'a,
+    {
+        let mut stack = vec![(0, 0, self.ids.len() - 1, root)];
+
+        Gen::new(|co| async move {
+            let hit = |i: usize, state: &S| {
+                if !self.is_live(i) {
+                    return None;
+                }
+                let result = matches(self.coords[2 * i], self.coords[2 * i + 1], state)?;
+                Some((self.ids.get(i) as usize, result))
+            };
+
+            while let Some((node, left, right, parent)) = stack.pop() {
+                let state = match descend(&self.extents[(4 * node)..(4 * node + 4)], &parent) {
+                    Some(state) => state,
+                    None => continue,
+                };
+
+                // if we reached "tree node", search linearly
+                if right - left <= self.node_size {
+                    for i in left..=right {
+                        if let Some(result) = hit(i, &state) {
+                            co.yield_(result).await;
+                        }
+                    }
+                    continue;
+                }
+
+                // otherwise check the middle item, and queue both halves
+                let m = (left + right) >> 1;
+                if let Some(result) = hit(m, &state) {
+                    co.yield_(result).await;
+                }
+
+                stack.push((2 * node + 2, m + 1, right, state.clone()));
+                stack.push((2 * node + 1, left, m - 1, state));
+            }
+        })
+        .into_iter()
+    }
+}
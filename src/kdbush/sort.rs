@@ -1,14 +1,31 @@
 use crate::{kdbush::AllowedNumber, util::IndexVec};
 
+// kd-sort ids and coords, recording the bounding box of every implicit subtree into `extents`
+// as it goes; subtrees are numbered like a binary heap (the children of `node` are `2 * node + 1`
+// and `2 * node + 2`) and each one takes up four consecutive slots
+#[allow(clippy::too_many_arguments)]
 pub fn sort_kd<T: AllowedNumber>(
     ids: &mut IndexVec,
     coords: &mut [T],
+    extents: &mut Vec<T>,
     node_size: usize,
+    node: usize,
     left: usize,
     right: usize,
     axis: usize,
 ) {
+    if extents.len() < 4 * (node + 1) {
+        // the filler value doesn't matter, since every slot for a reachable node gets written
+        extents.resize(4 * (node + 1), coords[0]);
+    }
+
     if right - left <= node_size {
+        let mut extent =
+            [coords[2 * left], coords[2 * left + 1], coords[2 * left], coords[2 * left + 1]];
+        for i in (left + 1)..=right {
+            extend(&mut extent, coords[2 * i], coords[2 * i + 1]);
+        }
+        extents[(4 * node)..(4 * node + 4)].copy_from_slice(&extent);
         return;
     }
 
@@ -19,8 +36,25 @@ pub fn sort_kd<T: AllowedNumber>(
     select(ids, coords, m, left, right, axis);
 
     // recursively kd-sort first half and second half on the opposite axis
-    sort_kd(ids, coords, node_size, left, m - 1, 1 - axis);
-    sort_kd(ids, coords, node_size, m + 1, right, 1 - axis);
+    sort_kd(ids, coords, extents, node_size, 2 * node + 1, left, m - 1, 1 - axis);
+    sort_kd(ids, coords, extents, node_size, 2 * node + 2, m + 1, right, 1 - axis);
+
+    // the subtree covers the middle point plus both halves
+    let mut extent = [coords[2 * m], coords[2 * m + 1], coords[2 * m], coords[2 * m + 1]];
+    for child in &[2 * node + 1, 2 * node + 2] {
+        let pos = 4 * child;
+        extend(&mut extent, extents[pos], extents[pos + 1]);
+        extend(&mut extent, extents[pos + 2], extents[pos + 3]);
+    }
+    extents[(4 * node)..(4 * node + 4)].copy_from_slice(&extent);
+}
+
+#[inline(always)]
+pub(super) fn extend<T: AllowedNumber>(extent: &mut [T; 4], x: T, y: T) {
+    extent[0] = min(extent[0], x);
+    extent[1] = min(extent[1], y);
+    extent[2] = max(extent[2], x);
+    extent[3] = max(extent[3], y);
 }
 
 // custom Floyd-Rivest selection algorithm: sort ids and coords so that
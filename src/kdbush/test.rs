@@ -1,6 +1,9 @@
 use once_cell::sync::Lazy;
 
+use core::ops::ControlFlow;
+
 use crate::kdbush::*;
+use crate::{OrientedRect, QueryScratch};
 
 #[rustfmt::skip]
 static POINTS: Lazy<Vec<[u32; 2]>> = Lazy::new(|| vec![
@@ -143,8 +146,667 @@ fn exact_search() {
     }
 }
 
+#[test]
+fn visitor_search() {
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(POINTS.iter());
+    let index = builder.finish();
+
+    let mut expected: Vec<_> = index.search_range(20, 30, 50, 70).collect();
+    let mut results = Vec::new();
+    let flow = index.visit_range(20, 30, 50, 70, |id| {
+        results.push(id);
+        ControlFlow::<()>::Continue(())
+    });
+    assert_eq!(flow, ControlFlow::Continue(()));
+    expected.sort();
+    results.sort();
+    assert_eq!(results, expected, "visits the same ids as search_range");
+
+    let mut expected: Vec<_> = index.search_within(50, 50, 20).collect();
+    let mut results = Vec::new();
+    let _ = index.visit_within(50, 50, 20, |id| {
+        results.push(id);
+        ControlFlow::<()>::Continue(())
+    });
+    expected.sort();
+    results.sort();
+    assert_eq!(results, expected, "visits the same ids as search_within");
+
+    let p = POINTS[42];
+    let flow = index.visit_exact(p[0], p[1], ControlFlow::Break);
+    assert_eq!(flow, ControlFlow::Break(42), "returns the break value");
+
+    let mut count = 0;
+    let flow = index.visit_range(0, 0, 100, 100, |id| {
+        count += 1;
+        if count == 5 {
+            ControlFlow::Break(id)
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert!(flow.is_break());
+    assert_eq!(count, 5, "stops as soon as the visitor breaks");
+}
+
+#[test]
+fn scratch_search() {
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(POINTS.iter());
+    let index = builder.finish();
+
+    let mut scratch = QueryScratch::new();
+
+    let result = index.search_range_with(20, 30, 50, 70, &mut scratch);
+    assert_eq!(result.len(), 18);
+    let mut result = result.to_vec();
+    let mut expected: Vec<_> = index.search_range(20, 30, 50, 70).collect();
+    result.sort();
+    expected.sort();
+    assert_eq!(result, expected, "range matches search_range");
+
+    let mut result = index.search_within_with(50, 50, 20, &mut scratch).to_vec();
+    let mut expected: Vec<_> = index.search_within(50, 50, 20).collect();
+    result.sort();
+    expected.sort();
+    assert_eq!(result, expected, "within matches search_within");
+
+    let p = POINTS[42];
+    assert_eq!(index.exact_with(p[0], p[1], &mut scratch), [42]);
+    assert_eq!(scratch.results(), [42], "scratch holds the latest results");
+}
+
+#[test]
+fn batch_search() {
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(POINTS.iter());
+    let index = builder.finish();
+
+    let ranges = [[20, 30, 50, 70], [0, 0, 10, 10], [90, 90, 95, 95], [0, 0, 100, 100]];
+    let results = index.search_range_batch(&ranges);
+    assert_eq!(results.len(), ranges.len());
+    assert_eq!(results.offsets.len(), ranges.len() + 1);
+    for (r, hits) in ranges.iter().zip(results.iter()) {
+        assert_eq!(hits, index.search_range(r[0], r[1], r[2], r[3]).collect::<Vec<_>>());
+    }
+
+    let circles = [([50, 50], 20), ([10, 10], 5), ([99, 2], 0)];
+    let results = index.search_within_batch(&circles);
+    for (i, (q, r)) in circles.iter().enumerate() {
+        let mut expected: Vec<_> = index.search_within(q[0], q[1], *r).collect();
+        let mut hits = results.get(i).to_vec();
+        expected.sort();
+        hits.sort();
+        assert_eq!(hits, expected);
+    }
+
+    let points: Vec<_> = POINTS.iter().cloned().collect();
+    let results = index.exact_batch(&points);
+    assert_eq!(results.ids, (0..POINTS.len()).collect::<Vec<_>>());
+
+    #[cfg(feature = "rayon")]
+    {
+        assert_eq!(index.par_search_range_batch(&ranges), index.search_range_batch(&ranges));
+        assert_eq!(index.par_search_within_batch(&circles), index.search_within_batch(&circles));
+        assert_eq!(index.par_exact_batch(&points), index.exact_batch(&points));
+    }
+}
+
+#[test]
+fn range_count() {
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(POINTS.iter());
+    let index = builder.finish();
+
+    let queries =
+        [[20, 30, 50, 70], [0, 0, 100, 100], [0, 0, 50, 50], [60, 10, 99, 40], [5, 5, 5, 5]];
+    for q in queries.iter() {
+        assert_eq!(
+            index.count_range(q[0], q[1], q[2], q[3]),
+            index.search_range(q[0], q[1], q[2], q[3]).count(),
+            "count matches search_range for {:?}",
+            q
+        );
+    }
+    assert_eq!(index.count_range(0, 0, 100, 100), POINTS.len());
+}
+
+#[test]
+fn weighted_top_k() {
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(POINTS.iter());
+    // a weight that doesn't follow either coordinate, with some ties
+    let weights: Vec<u32> = (0..POINTS.len() as u32).map(|i| (i * 37) % 50).collect();
+    let index = builder.finish_with_weights(weights.clone());
+
+    for q in [[20, 30, 50, 70], [0, 0, 100, 100], [60, 10, 99, 40], [5, 5, 5, 5]].iter() {
+        let mut expected: Vec<_> =
+            index.search_range(q[0], q[1], q[2], q[3]).map(|id| weights[id]).collect();
+        expected.sort_by(|a, b| b.cmp(a));
+
+        for &k in &[0, 1, 5, 1000, usize::MAX] {
+            let results = index.top_k_range(q[0], q[1], q[2], q[3], k);
+            assert_eq!(
+                results.iter().map(|(_, w)| *w).collect::<Vec<_>>(),
+                expected.iter().take(k).cloned().collect::<Vec<_>>(),
+                "top {} weights in {:?}",
+                k,
+                q
+            );
+            for (id, w) in results {
+                assert_eq!(weights[id], w);
+                let p = POINTS[id];
+                assert!(p[0] >= q[0] && p[0] <= q[2] && p[1] >= q[1] && p[1] <= q[3]);
+            }
+        }
+    }
+}
+
+#[test]
+fn dynamic_insert_and_remove() {
+    let mut index = DynamicKDBush::new_with_node_size(10);
+
+    // enough copies of the points to go through several merges
+    let mut points = Vec::new();
+    for copy in 0..10 {
+        for p in POINTS.iter() {
+            let id = index.insert(p);
+            assert_eq!(id, points.len());
+            points.push(*p);
+            if (id + copy) % 3 == 0 {
+                assert!(index.remove(id));
+            }
+        }
+    }
+    // and remove some points that have already been merged into static levels
+    for id in (0..points.len()).step_by(7) {
+        index.remove(id);
+    }
+    assert!(!index.remove(0), "removing twice is a no-op");
+    assert!(!index.remove(points.len()), "removing an unknown id is a no-op");
+    assert!(!index.contains(points.len()));
+
+    let live: Vec<usize> = (0..points.len()).filter(|id| index.contains(*id)).collect();
+    assert_eq!(index.len(), live.len());
+
+    let mut result: Vec<_> = index.search_range(20, 30, 50, 70).collect();
+    let mut expected: Vec<_> = live
+        .iter()
+        .cloned()
+        .filter(|id| {
+            let p = points[*id];
+            p[0] >= 20 && p[0] <= 50 && p[1] >= 30 && p[1] <= 70
+        })
+        .collect();
+    result.sort();
+    expected.sort();
+    assert_eq!(result, expected, "range search sees only live points");
+
+    let mut result: Vec<_> = index.search_within(50, 50, 20).collect();
+    let mut expected: Vec<_> =
+        live.iter().cloned().filter(|id| sq_dist(points[*id], [50, 50]) <= 400).collect();
+    result.sort();
+    expected.sort();
+    assert_eq!(result, expected, "radius search sees only live points");
+
+    let p = POINTS[42];
+    let mut result: Vec<_> = index.exact(p[0], p[1]).collect();
+    let mut expected: Vec<_> = live.iter().cloned().filter(|id| points[*id] == p).collect();
+    result.sort();
+    expected.sort();
+    assert_eq!(result, expected, "exact search sees only live points");
+}
+
+#[test]
+fn dynamic_query_variants() {
+    let mut index = DynamicKDBush::new_with_buffer_size(10, 16);
+    for p in POINTS.iter() {
+        index.insert(p);
+    }
+    for id in (0..POINTS.len()).step_by(3) {
+        index.remove(id);
+    }
+
+    let sorted = |mut ids: Vec<usize>| {
+        ids.sort();
+        ids
+    };
+    let range = sorted(index.search_range(20, 30, 50, 70).collect());
+    let within = sorted(index.search_within(50, 50, 20).collect());
+    let p = POINTS[42];
+    let exact = sorted(index.exact(p[0], p[1]).collect());
+
+    let mut visited = Vec::new();
+    let _ = index.visit_range::<(), _>(20, 30, 50, 70, |id| {
+        visited.push(id);
+        ControlFlow::Continue(())
+    });
+    assert_eq!(sorted(visited), range);
+    assert_eq!(index.count_range(20, 30, 50, 70), range.len());
+    match index.visit_within(50, 50, 20, ControlFlow::Break) {
+        ControlFlow::Break(id) => assert!(within.contains(&id)),
+        ControlFlow::Continue(()) => panic!("visiting stops at the first result"),
+    }
+
+    let mut scratch = QueryScratch::new();
+    assert_eq!(sorted(index.search_range_with(20, 30, 50, 70, &mut scratch).to_vec()), range);
+    assert_eq!(sorted(index.search_within_with(50, 50, 20, &mut scratch).to_vec()), within);
+    assert_eq!(sorted(index.exact_with(p[0], p[1], &mut scratch).to_vec()), exact);
+
+    let results = index.search_range_batch(&[[20, 30, 50, 70]]);
+    assert_eq!(sorted(results.get(0).to_vec()), range);
+    let results = index.search_within_batch(&[([50, 50], 20)]);
+    assert_eq!(sorted(results.get(0).to_vec()), within);
+    let results = index.exact_batch(&[p]);
+    assert_eq!(sorted(results.get(0).to_vec()), exact);
+
+    // removing most of the points rebuilds or drops the levels they were in
+    for id in 0..POINTS.len() - 6 {
+        index.remove(id);
+    }
+    let live: Vec<_> =
+        (0..POINTS.len()).filter(|id| id % 3 != 0 && *id >= POINTS.len() - 6).collect();
+    assert_eq!(index.len(), live.len());
+    assert!(index.num_stored() < 15, "removed points are reclaimed");
+    assert_eq!(sorted(index.search_range(0, 0, 100, 100).collect()), live);
+}
+
+#[test]
+fn delete_and_compact() {
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(POINTS.iter());
+    let mut index = builder.finish();
+
+    let all: Vec<_> = index.search_range(20, 30, 50, 70).collect();
+    for id in all.iter().step_by(2) {
+        assert!(index.delete(*id));
+    }
+    assert!(!index.delete(all[0]), "deleting twice is a no-op");
+    assert!(index.is_deleted(all[0]));
+    assert_eq!(index.num_deleted(), 9);
+    assert_eq!(index.deleted_ratio(), 0.09);
+
+    let mut expected: Vec<_> = all.iter().cloned().skip(1).step_by(2).collect();
+    expected.sort();
+
+    let check = |index: &KDBush<u32>| {
+        let mut result: Vec<_> = index.search_range(20, 30, 50, 70).collect();
+        result.sort();
+        assert_eq!(result, expected, "search_range skips deleted points");
+
+        let mut result = index.search_range_with(20, 30, 50, 70, &mut QueryScratch::new()).to_vec();
+        result.sort();
+        assert_eq!(result, expected, "search_range_with skips deleted points");
+
+        let mut result = Vec::new();
+        let _ = index.visit_range(20, 30, 50, 70, |id| {
+            result.push(id);
+            ControlFlow::<()>::Continue(())
+        });
+        result.sort();
+        assert_eq!(result, expected, "visit_range skips deleted points");
+
+        assert_eq!(index.count_range(20, 30, 50, 70), expected.len());
+        assert_eq!(index.count_range(0, 0, 100, 100), 91);
+
+        let p = POINTS[all[0]];
+        assert_eq!(index.exact(p[0], p[1]).count(), 0);
+        assert_eq!(index.exact_as_vec(p[0], p[1]).len(), 0);
+        let p = POINTS[all[1]];
+        assert_eq!(index.exact(p[0], p[1]).collect::<Vec<_>>(), [all[1]]);
+    };
+
+    check(&index);
+
+    let compacted = index.compact();
+    assert_eq!(compacted.num_items(), 91);
+    assert_eq!(compacted.num_deleted(), 0);
+    check(&compacted);
+
+    let mut result: Vec<_> = compacted.search_within(50, 50, 20).collect();
+    let mut expected: Vec<_> = index.search_within(50, 50, 20).collect();
+    result.sort();
+    expected.sort();
+    assert_eq!(result, expected, "compacted index keeps the original ids");
+}
+
+#[test]
+fn looks_up_points_by_id() {
+    for &id_lookup in &[false, true] {
+        let mut builder = KDBushBuilder::new_with_node_size(10);
+        builder.set_id_lookup(id_lookup);
+        builder.extend(POINTS.iter());
+        let mut index = builder.finish();
+        assert_eq!(index.positions.is_some(), id_lookup);
+
+        for (id, p) in POINTS.iter().enumerate() {
+            assert_eq!(index.point(id), Some(*p));
+        }
+        assert_eq!(index.point(POINTS.len()), None);
+
+        let ids: Vec<_> = index.iter().map(|(id, _)| id as u32).collect();
+        assert_eq!(ids, *IDS, "iterates in kd-sorted order");
+        assert!(index.iter().all(|(id, p)| POINTS[id] == p));
+
+        index.delete(3);
+        assert_eq!(index.point(3), None);
+        assert_eq!(index.iter().count(), POINTS.len() - 1);
+
+        let compacted = index.compact();
+        assert_eq!(compacted.positions.is_some(), id_lookup);
+        assert_eq!(compacted.point(3), None);
+        assert_eq!(compacted.point(4), Some(POINTS[4]));
+        assert_eq!(compacted.point(99), Some(POINTS[99]));
+    }
+}
+
+#[test]
+fn searches_with_coordinates() {
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(POINTS.iter());
+    let index = builder.finish();
+
+    let results: Vec<_> = index.search_range_points(20, 30, 50, 70).collect();
+    assert_eq!(
+        results.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        index.search_range(20, 30, 50, 70).collect::<Vec<_>>()
+    );
+    assert!(results.iter().all(|(id, p)| POINTS[*id] == *p));
+
+    let results: Vec<_> = index.search_within_points(50, 50, 20).collect();
+    assert_eq!(
+        results.iter().map(|(id, _, _)| *id).collect::<Vec<_>>(),
+        index.search_within(50, 50, 20).collect::<Vec<_>>()
+    );
+    assert!(results.iter().all(|(id, p, d2)| POINTS[*id] == *p && sq_dist(*p, [50, 50]) == *d2));
+}
+
+#[allow(clippy::manual_abs_diff)]
 fn sq_dist(a: [u32; 2], b: [u32; 2]) -> u32 {
     let dx = if a[0] > b[0] { a[0] - b[0] } else { b[0] - a[0] };
     let dy = if a[1] > b[1] { a[1] - b[1] } else { b[1] - a[1] };
     dx * dx + dy * dy
 }
+
+#[test]
+fn searches_range_across_antimeridian() {
+    let points: Vec<[f64; 2]> = (0..360).map(|i| [-179.5 + i as f64, (i % 7) as f64]).collect();
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(points.iter());
+    let index = builder.finish();
+
+    let mut results: Vec<_> = index.geo_search_range(175., 0., -175., 6.).collect();
+    results.sort_unstable();
+    let expected: Vec<_> =
+        (0..360).filter(|&i| points[i][0] >= 175. || points[i][0] <= -175.).collect();
+    assert_eq!(results, expected);
+
+    // an ordinary range behaves like `search_range`
+    let mut results: Vec<_> = index.geo_search_range(-10., 2., 10., 4.).collect();
+    results.sort_unstable();
+    let mut expected: Vec<_> = index.search_range(-10., 2., 10., 4.).collect();
+    expected.sort_unstable();
+    assert_eq!(results, expected);
+}
+
+#[test]
+fn searches_corridor_along_polyline() {
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(POINTS.iter());
+    let index = builder.finish();
+
+    let line = [[10, 10], [60, 20], [60, 80], [90, 90]];
+    let mut results: Vec<_> = index.search_corridor(&line, 5).collect();
+    results.sort_by_key(|&(id, _, _)| id);
+
+    let expected: Vec<_> = POINTS
+        .iter()
+        .enumerate()
+        .filter_map(|(id, p)| {
+            let p = [p[0] as f64, p[1] as f64];
+            let (dist, segment) = line
+                .windows(2)
+                .enumerate()
+                .map(|(s, w)| {
+                    let (a, b) =
+                        ([w[0][0] as f64, w[0][1] as f64], [w[1][0] as f64, w[1][1] as f64]);
+                    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+                    let t = (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / (dx * dx + dy * dy))
+                        .clamp(0., 1.);
+                    (((a[0] + t * dx - p[0]).powi(2) + (a[1] + t * dy - p[1]).powi(2)).sqrt(), s)
+                })
+                .fold((f64::INFINITY, 0), |a, b| if b.0 < a.0 { b } else { a });
+            if dist <= 5. {
+                Some((id, dist, segment))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    assert!(!expected.is_empty());
+    assert_eq!(results.len(), expected.len());
+    for (result, expected) in results.iter().zip(expected.iter()) {
+        assert_eq!(result.0, expected.0);
+        assert!((result.1 - expected.1).abs() < 1e-9);
+        assert_eq!(result.2, expected.2);
+    }
+
+    // a single point works like `search_within`
+    let mut results: Vec<_> = index.search_corridor(&[[50, 50]], 20).map(|(id, _, _)| id).collect();
+    results.sort_unstable();
+    let mut expected: Vec<_> = index.search_within(50, 50, 20).collect();
+    expected.sort_unstable();
+    assert_eq!(results, expected);
+}
+
+#[test]
+fn performs_annulus_search() {
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(POINTS.iter());
+    let index = builder.finish();
+
+    let mut results: Vec<_> = index.search_annulus(50, 50, 10, 30).collect();
+    results.sort_unstable();
+    let expected: Vec<_> = (0..POINTS.len())
+        .filter(|&i| {
+            let d2 = sq_dist(POINTS[i], [50, 50]);
+            (100..=900).contains(&d2)
+        })
+        .collect();
+    assert!(!expected.is_empty());
+    assert_eq!(results, expected);
+
+    // with no inner radius, it's the same as `search_within`
+    let mut results: Vec<_> = index.search_annulus(30, 70, 0, 25).collect();
+    results.sort_unstable();
+    let mut expected: Vec<_> = index.search_within(30, 70, 25).collect();
+    expected.sort_unstable();
+    assert_eq!(results, expected);
+}
+
+#[test]
+fn performs_radius_search_with_metrics() {
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(POINTS.iter());
+    let index = builder.finish();
+
+    let check = |metric: &dyn Fn(u32, u32) -> bool, results: Vec<usize>| {
+        let mut results = results;
+        results.sort_unstable();
+        let expected: Vec<_> = (0..POINTS.len())
+            .filter(|&i| metric(POINTS[i][0].abs_diff(40), POINTS[i][1].abs_diff(60)))
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(results, expected);
+    };
+
+    check(&|dx, dy| dx + dy <= 20, index.search_within_metric(40, 60, 20, Manhattan).collect());
+    check(&|dx, dy| dx.max(dy) <= 20, index.search_within_metric(40, 60, 20, Chebyshev).collect());
+    check(
+        &|dx, dy| dx * dx + dy * dy <= 400,
+        index.search_within_metric(40, 60, 20, Euclidean).collect(),
+    );
+    // an ellipse twice as wide as it's tall
+    let ellipse = WeightedEuclidean { weights: [1, 2] };
+    check(
+        &|dx, dy| dx * dx + 4 * dy * dy <= 400,
+        index.search_within_metric(40, 60, 20, ellipse).collect(),
+    );
+
+    let mut results = Vec::new();
+    let _: ControlFlow<()> = index.visit_within_metric(40, 60, 20, Chebyshev, |id| {
+        results.push(id);
+        ControlFlow::Continue(())
+    });
+    check(&|dx, dy| dx.max(dy) <= 20, results);
+
+    // the Euclidean metric is what `search_within` uses
+    assert_eq!(
+        index.search_within(40, 60, 20).collect::<Vec<_>>(),
+        index.search_within_metric(40, 60, 20, Euclidean).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn performs_oriented_rect_search() {
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(POINTS.iter());
+    let index = builder.finish();
+
+    // a long, thin rectangle along the diagonal
+    let rect = OrientedRect::new([50., 50.], [80., 20.], std::f64::consts::FRAC_PI_4);
+    let mut results: Vec<_> = index.search_oriented(rect).collect();
+    results.sort_unstable();
+
+    let (c, s) = (std::f64::consts::FRAC_1_SQRT_2, std::f64::consts::FRAC_1_SQRT_2);
+    let expected: Vec<_> = (0..POINTS.len())
+        .filter(|&i| {
+            let (dx, dy) = (POINTS[i][0] as f64 - 50., POINTS[i][1] as f64 - 50.);
+            (dx * c + dy * s).abs() <= 40. && (-dx * s + dy * c).abs() <= 10.
+        })
+        .collect();
+    assert!(!expected.is_empty());
+    assert_eq!(results, expected);
+
+    // and fewer than the rectangle's bounding box holds
+    let [min_x, min_y, max_x, max_y] = rect.bbox();
+    let in_bbox = index
+        .search_range(min_x.ceil() as u32, min_y.ceil() as u32, max_x as u32, max_y as u32)
+        .count();
+    assert!(results.len() < in_bbox);
+}
+
+#[test]
+fn searches_within_near_origin_unsigned() {
+    let mut builder = KDBushBuilder::new_with_node_size(10);
+    builder.extend(POINTS.iter());
+    let index = builder.finish();
+
+    let expected: Vec<_> = {
+        let mut ids: Vec<_> =
+            (0..POINTS.len()).filter(|&i| sq_dist(POINTS[i], [2, 2]) <= 25 * 25).collect();
+        ids.sort_unstable();
+        ids
+    };
+    assert!(!expected.is_empty());
+
+    let mut results: Vec<_> = index.search_within_points(2, 2, 25).map(|(id, _, _)| id).collect();
+    results.sort_unstable();
+    assert_eq!(results, expected);
+
+    let mut results: Vec<_> = index.search_within(2, 2, 25).collect();
+    results.sort_unstable();
+    assert_eq!(results, expected);
+}
+
+// check that every subtree is partitioned around its middle point and that its extent is its
+// points' bounding box
+fn assert_kd_layout(index: &KDBush<u32>) {
+    fn check(index: &KDBush<u32>, node: usize, left: usize, right: usize, axis: usize) {
+        let xs = (left..=right).map(|i| index.coords[2 * i]);
+        let ys = (left..=right).map(|i| index.coords[2 * i + 1]);
+        let extent = [xs.clone().min(), ys.clone().min(), xs.max(), ys.max()].map(Option::unwrap);
+        assert_eq!(index.extents[(4 * node)..(4 * node + 4)], extent);
+
+        if right - left <= index.node_size {
+            return;
+        }
+        let m = (left + right) >> 1;
+        let split = index.coords[2 * m + axis];
+        assert!((left..m).all(|i| index.coords[2 * i + axis] <= split));
+        assert!((m + 1..=right).all(|i| index.coords[2 * i + axis] >= split));
+        check(index, 2 * node + 1, left, m - 1, 1 - axis);
+        check(index, 2 * node + 2, m + 1, right, 1 - axis);
+    }
+    check(index, 0, 0, index.ids.len() - 1, 0);
+}
+
+#[test]
+fn compact_reuses_kd_layout() {
+    let mut seed = 7u32;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed % 1000
+    };
+    let points: Vec<[u32; 2]> = (0..5000).map(|_| [next(), next()]).collect();
+
+    let mut builder = KDBushBuilder::new_with_node_size(4);
+    builder.extend(points.iter());
+    let index = builder.finish();
+    assert_kd_layout(&index);
+
+    // scattered deletions need only small fixes, while deleting a whole region makes some
+    // subtrees too lopsided to fix up
+    let patterns: [&dyn Fn(usize) -> bool; 4] =
+        [&|id| id % 3 == 0, &|id| id % 97 == 5, &|id| points[id][0] < 300, &|id| {
+            id % 2 == 0 || points[id][1] > 800
+        }];
+    for deleted in patterns.iter() {
+        let mut index = index.clone();
+        for id in (0..points.len()).filter(|id| deleted(*id)) {
+            assert!(index.delete(id));
+        }
+
+        let compacted = index.compact();
+        assert_kd_layout(&compacted);
+
+        let mut result: Vec<_> = compacted.iter().collect();
+        result.sort_unstable();
+        let expected: Vec<_> =
+            (0..points.len()).filter(|id| !deleted(*id)).map(|id| (id, points[id])).collect();
+        assert_eq!(result, expected);
+
+        let mut result: Vec<_> = compacted.search_range(200, 300, 600, 700).collect();
+        result.sort_unstable();
+        let mut expected: Vec<_> = index.search_range(200, 300, 600, 700).collect();
+        expected.sort_unstable();
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn ignores_deleting_unknown_ids() {
+    for &id_lookup in &[false, true] {
+        let mut builder = KDBushBuilder::new();
+        builder.set_id_lookup(id_lookup);
+        builder.extend([[1, 1], [2, 2]].iter());
+        let mut index = builder.finish();
+
+        assert!(!index.delete(1000));
+        assert!(!index.delete(2));
+        assert_eq!(index.num_deleted(), 0);
+        assert_eq!(index.deleted_ratio(), 0.);
+
+        // ids dropped by a compaction aren't in the index anymore either
+        assert!(index.delete(0));
+        let mut compacted = index.compact();
+        assert!(!compacted.delete(0));
+        assert_eq!(compacted.num_deleted(), 0);
+        assert!(compacted.delete(1));
+        assert_eq!(compacted.deleted_ratio(), 1.);
+    }
+}
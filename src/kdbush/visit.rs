@@ -0,0 +1,55 @@
+use crate::kdbush::{AllowedNumber, KDBush};
+
+use core::ops::ControlFlow;
+
+impl<T: AllowedNumber> KDBush<T> {
+    // recursive, allocation-free counterpart to the stack-based searches: `contains` decides
+    // whether a point is a hit, and `split` decides which halves of a node to descend into given
+    // the axis and the node's middle point
+    pub(super) fn walk<B, P, S, F>(
+        &self,
+        left: usize,
+        right: usize,
+        axis: usize,
+        contains: &P,
+        split: &S,
+        visitor: &mut F,
+    ) -> ControlFlow<B>
+    where
+        P: Fn(T, T) -> bool,
+        S: Fn(usize, T, T) -> (bool, bool),
+        F: FnMut(usize) -> ControlFlow<B>,
+    {
+        // if we reached "tree node", search linearly
+        if right - left <= self.node_size {
+            for i in left..=right {
+                if contains(self.coords[2 * i], self.coords[2 * i + 1]) && self.is_live(i) {
+                    visitor(self.ids.get(i) as usize)?;
+                }
+            }
+            return ControlFlow::Continue(());
+        }
+
+        // otherwise find the middle index
+        let m = (left + right) >> 1;
+
+        // include the middle item if it's in range
+        let x = self.coords[2 * m];
+        let y = self.coords[2 * m + 1];
+        if contains(x, y) && self.is_live(m) {
+            visitor(self.ids.get(m) as usize)?;
+        }
+
+        // search the halves that intersect the query
+        let (over_min, under_max) = split(axis, x, y);
+
+        if over_min {
+            self.walk(left, m - 1, 1 - axis, contains, split, visitor)?;
+        }
+        if under_max {
+            self.walk(m + 1, right, 1 - axis, contains, split, visitor)?;
+        }
+
+        ControlFlow::Continue(())
+    }
+}
@@ -0,0 +1,190 @@
+use core::cmp::Ordering;
+use core::ops::Deref;
+use std::collections::BinaryHeap;
+
+use crate::kdbush::{AllowedNumber, KDBush, KDBushBuilder};
+
+/// A `KDBush` whose points each carry a weight, with the maximum weight of every implicit kd
+/// subtree stored alongside it so that the heaviest points in a region can be found without
+/// scanning the whole region. Dereferences to the underlying `KDBush`.
+#[derive(Clone)]
+pub struct WeightedKDBush<T: AllowedNumber, W: PartialOrd + Copy> {
+    bush: KDBush<T>,
+    // one weight per point, in kd-sorted order
+    weights: Vec<W>,
+    // maximum weight of each implicit subtree, in the same heap order as `bush.extents`
+    max_weights: Vec<W>,
+}
+
+impl<T: AllowedNumber> KDBushBuilder<T> {
+    /// Build the index along with per-subtree maximums of `weights`, which must hold one weight
+    /// per added point, in the order the points were added.
+    pub fn finish_with_weights<W: PartialOrd + Copy>(
+        self,
+        weights: Vec<W>,
+    ) -> WeightedKDBush<T, W> {
+        assert_eq!(weights.len(), self.coords.len() >> 1, "expected one weight per point");
+
+        let bush = self.finish();
+        let weights: Vec<W> =
+            (0..bush.ids.len()).map(|i| weights[bush.ids.get(i) as usize]).collect();
+
+        let mut max_weights = vec![weights[0]; bush.extents.len() >> 2];
+        fill_max_weights(&weights, &mut max_weights, bush.node_size, 0, 0, bush.ids.len() - 1);
+
+        WeightedKDBush { bush, weights, max_weights }
+    }
+}
+
+fn fill_max_weights<W: PartialOrd + Copy>(
+    weights: &[W],
+    max_weights: &mut [W],
+    node_size: usize,
+    node: usize,
+    left: usize,
+    right: usize,
+) -> W {
+    let mut max_weight = weights[left];
+
+    if right - left <= node_size {
+        for w in &weights[left..=right] {
+            if *w > max_weight {
+                max_weight = *w;
+            }
+        }
+    } else {
+        let m = (left + right) >> 1;
+        max_weight = weights[m];
+        for w in &[
+            fill_max_weights(weights, max_weights, node_size, 2 * node + 1, left, m - 1),
+            fill_max_weights(weights, max_weights, node_size, 2 * node + 2, m + 1, right),
+        ] {
+            if *w > max_weight {
+                max_weight = *w;
+            }
+        }
+    }
+
+    max_weights[node] = max_weight;
+    max_weight
+}
+
+// a pending entry in the best-first search: either a whole subtree, keyed by its maximum weight,
+// or a single point that's already known to be in range, keyed by its own weight
+struct Candidate<W> {
+    weight: W,
+    node: usize,
+    left: usize,
+    right: usize,
+    is_point: bool,
+}
+
+impl<W: PartialOrd> PartialEq for Candidate<W> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<W: PartialOrd> Eq for Candidate<W> {}
+
+impl<W: PartialOrd> PartialOrd for Candidate<W> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<W: PartialOrd> Ord for Candidate<W> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // points win ties against subtrees, so that they're returned as soon as they're certain
+        self.weight
+            .partial_cmp(&other.weight)
+            .unwrap_or(Ordering::Equal)
+            .then(self.is_point.cmp(&other.is_point))
+    }
+}
+
+impl<T: AllowedNumber, W: PartialOrd + Copy> WeightedKDBush<T, W> {
+    /// The (up to) `k` heaviest points in the given range, as `(id, weight)` pairs in descending
+    /// order of weight.
+    pub fn top_k_range(&self, min_x: T, min_y: T, max_x: T, max_y: T, k: usize) -> Vec<(usize, W)> {
+        let bush = &self.bush;
+        let mut results = Vec::with_capacity(k.min(bush.ids.len()));
+        if k == 0 {
+            return results;
+        }
+
+        let in_range = |i: usize| {
+            let x = bush.coords[2 * i];
+            let y = bush.coords[2 * i + 1];
+            x >= min_x && x <= max_x && y >= min_y && y <= max_y
+        };
+        let point = |i: usize| Candidate {
+            weight: self.weights[i],
+            node: 0,
+            left: i,
+            right: i,
+            is_point: true,
+        };
+
+        let mut queue = BinaryHeap::new();
+        queue.push(Candidate {
+            weight: self.max_weights[0],
+            node: 0,
+            left: 0,
+            right: bush.ids.len() - 1,
+            is_point: false,
+        });
+
+        while let Some(candidate) = queue.pop() {
+            let Candidate { node, left, right, .. } = candidate;
+
+            // nothing left in the queue can outweigh a point at the front of it
+            if candidate.is_point {
+                results.push((bush.ids.get(left) as usize, candidate.weight));
+                if results.len() == k {
+                    break;
+                }
+                continue;
+            }
+
+            // skip subtrees that miss the query entirely
+            let extent = &bush.extents[(4 * node)..(4 * node + 4)];
+            if extent[0] > max_x || extent[1] > max_y || extent[2] < min_x || extent[3] < min_y {
+                continue;
+            }
+
+            // if we reached "tree node", queue its points that are in range
+            if right - left <= bush.node_size {
+                queue.extend((left..=right).filter(|i| in_range(*i)).map(point));
+                continue;
+            }
+
+            // otherwise queue the middle item and both halves
+            let m = (left + right) >> 1;
+            if in_range(m) {
+                queue.push(point(m));
+            }
+            for &(child, left, right) in
+                &[(2 * node + 1, left, m - 1), (2 * node + 2, m + 1, right)]
+            {
+                queue.push(Candidate {
+                    weight: self.max_weights[child],
+                    node: child,
+                    left,
+                    right,
+                    is_point: false,
+                });
+            }
+        }
+
+        results
+    }
+}
+
+impl<T: AllowedNumber, W: PartialOrd + Copy> Deref for WeightedKDBush<T, W> {
+    type Target = KDBush<T>;
+
+    fn deref(&self) -> &KDBush<T> {
+        &self.bush
+    }
+}
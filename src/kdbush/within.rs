@@ -1,62 +1,162 @@
-use crate::kdbush::{AllowedNumber, KDBush};
+use crate::kdbush::{AllowedNumber, Euclidean, KDBush, Metric};
+use crate::QueryScratch;
 
-use genawaiter::rc::Gen;
+use core::ops::ControlFlow;
 
 impl<T: AllowedNumber> KDBush<T> {
     pub fn search_within<'a>(&'a self, qx: T, qy: T, r: T) -> impl Iterator<Item = usize> + 'a {
-        let mut stack = vec![0, self.ids.len() - 1, 0];
+        self.search_within_metric(qx, qy, r, Euclidean)
+    }
+
+    pub fn visit_within<B, F: FnMut(usize) -> ControlFlow<B>>(
+        &self,
+        qx: T,
+        qy: T,
+        r: T,
+        visitor: F,
+    ) -> ControlFlow<B> {
+        self.visit_within_metric(qx, qy, r, Euclidean, visitor)
+    }
+
+    pub fn search_within_with<'s>(
+        &self,
+        qx: T,
+        qy: T,
+        r: T,
+        scratch: &'s mut QueryScratch,
+    ) -> &'s [usize] {
+        let (contains, split) = within_tests(qx, qy, r, Euclidean);
+        self.search_into(scratch, contains, split)
+    }
+
+    /// Like `search_within`, but measures distance with the given metric.
+    pub fn search_within_metric<'a, M: Metric<T> + Clone + 'a>(
+        &'a self,
+        qx: T,
+        qy: T,
+        r: T,
+        metric: M,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let (contains, split) = within_tests(qx, qy, r, metric);
+        self.search(move |id, x, y| if contains(x, y) { Some(id) } else { None }, split)
+    }
+
+    /// Like `visit_within`, but measures distance with the given metric.
+    pub fn visit_within_metric<B, M: Metric<T> + Clone, F: FnMut(usize) -> ControlFlow<B>>(
+        &self,
+        qx: T,
+        qy: T,
+        r: T,
+        metric: M,
+        mut visitor: F,
+    ) -> ControlFlow<B> {
+        let (contains, split) = within_tests(qx, qy, r, metric);
+        self.walk(0, self.ids.len() - 1, 0, &contains, &split, &mut visitor)
+    }
+
+    /// Search for points at least `r_min` and at most `r_max` from the query point. Subtrees
+    /// that lie entirely inside the inner radius are skipped, as well as those entirely outside
+    /// the outer one.
+    pub fn search_annulus<'a>(
+        &'a self,
+        qx: T,
+        qy: T,
+        r_min: T,
+        r_max: T,
+    ) -> impl Iterator<Item = usize> + 'a {
+        let (r2_min, r2_max) = (r_min * r_min, r_max * r_max);
+
+        self.search_extents(
+            move |extent| {
+                let (near, far) = box_sq_dist_range(qx, qy, extent);
+                near <= r2_max && far >= r2_min
+            },
+            move |x, y| {
+                let d2 = sq_dist(x, y, qx, qy);
+                d2 >= r2_min && d2 <= r2_max
+            },
+        )
+    }
+
+    /// Like `search_within`, but yields each point's coordinates and squared distance from the
+    /// query point along with its id.
+    pub fn search_within_points<'a>(
+        &'a self,
+        qx: T,
+        qy: T,
+        r: T,
+    ) -> impl Iterator<Item = (usize, [T; 2], T)> + 'a {
         let r2 = r * r;
+        let (_, split) = within_tests(qx, qy, r, Euclidean);
 
-        Gen::new(|co| async move {
-            // recursively search for items in range in the kd-sorted arrays
-            while stack.len() > 0 {
-                // we always push three at a time, so pops three at a time will always work -- unwrap
-                // is safe here
-                let axis = stack.pop().unwrap();
-                let right = stack.pop().unwrap();
-                let left = stack.pop().unwrap();
-
-                // if we reached "tree node", search linearly
-                if right - left <= self.node_size {
-                    for i in left..=right {
-                        if sq_dist(self.coords[2 * i], self.coords[2 * i + 1], qx, qy) <= r2 {
-                            co.yield_(self.ids.get(i) as usize).await;
-                        }
-                    }
-                    continue;
+        self.search(
+            move |id, x, y| {
+                let d2 = sq_dist(x, y, qx, qy);
+                if d2 <= r2 {
+                    Some((id, [x, y], d2))
+                } else {
+                    None
                 }
+            },
+            split,
+        )
+    }
+}
 
-                // otherwise find the middle index
-                let m = (left + right) >> 1;
+// the point and split tests for a radius query under `metric`, in the form `walk` and `search`
+// take them
+#[allow(clippy::type_complexity)]
+fn within_tests<T: AllowedNumber, M: Metric<T> + Clone>(
+    qx: T,
+    qy: T,
+    r: T,
+    metric: M,
+) -> (impl Fn(T, T) -> bool, impl Fn(usize, T, T) -> (bool, bool)) {
+    let r = metric.radius(r);
+    let split_metric = metric.clone();
 
-                // include the middle item if it's in range
-                let x = self.coords[2 * m];
-                let y = self.coords[2 * m + 1];
-                if sq_dist(x, y, qx, qy) <= r2 {
-                    co.yield_(self.ids.get(m) as usize).await;
-                }
+    let contains = move |x, y| metric.distance(abs_diff(x, qx), abs_diff(y, qy)) <= r;
+    let split = move |axis, x, y| {
+        let (q, k) = if axis == 0 { (qx, x) } else { (qy, y) };
+        // the lower half holds coordinates up to the split, and the upper half from it on
+        (
+            q <= k || split_metric.split_distance(axis, q - k) <= r,
+            q >= k || split_metric.split_distance(axis, k - q) <= r,
+        )
+    };
 
-                // queue search in halves that intersect the query
-                let (over_min, under_max) =
-                    if axis == 0 { (qx - r <= x, qx + r >= x) } else { (qy - r <= y, qy + r >= y) };
+    (contains, split)
+}
 
-                if over_min {
-                    stack.push(left);
-                    stack.push(m - 1);
-                    stack.push(1 - axis);
-                }
-                if under_max {
-                    stack.push(m + 1);
-                    stack.push(right);
-                    stack.push(1 - axis);
-                }
-            }
-        })
-        .into_iter()
+// squared distances from a point to the nearest and farthest points of a box
+fn box_sq_dist_range<T: AllowedNumber>(qx: T, qy: T, b: &[T]) -> (T, T) {
+    let (near_x, far_x) = axis_dist_range(qx, b[0], b[2]);
+    let (near_y, far_y) = axis_dist_range(qy, b[1], b[3]);
+    (near_x * near_x + near_y * near_y, far_x * far_x + far_y * far_y)
+}
+
+fn axis_dist_range<T: AllowedNumber>(k: T, min: T, max: T) -> (T, T) {
+    // again written to keep unsigned types from overflowing
+    let nearest = if k < min {
+        min
+    } else if k > max {
+        max
+    } else {
+        k
+    };
+    let (to_min, to_max) = (abs_diff(k, min), abs_diff(k, max));
+    (abs_diff(k, nearest), if to_min > to_max { to_min } else { to_max })
+}
+
+fn abs_diff<T: AllowedNumber>(a: T, b: T) -> T {
+    if a > b {
+        a - b
+    } else {
+        b - a
     }
 }
 
-fn sq_dist<T: AllowedNumber>(ax: T, ay: T, bx: T, by: T) -> T {
+pub(super) fn sq_dist<T: AllowedNumber>(ax: T, ay: T, bx: T, by: T) -> T {
     // T might be unsigned, so we need to jump through some hoops to keep from overflowing
     // (in the future it might make sense to specialize here, and do a faster one for signed ints)
     let dx = if ax > bx { ax - bx } else { bx - ax };
@@ -1,6 +1,14 @@
+mod batch;
+mod dynamic;
 pub mod flatbush;
+pub mod geo;
+mod geometry;
 pub mod kdbush;
+mod scratch;
 mod util;
 
+pub use crate::batch::BatchResults;
 pub use crate::flatbush::{FlatBush, FlatBushBuilder};
+pub use crate::geometry::OrientedRect;
 pub use crate::kdbush::{KDBush, KDBushBuilder};
+pub use crate::scratch::QueryScratch;
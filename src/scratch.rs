@@ -0,0 +1,31 @@
+/// Reusable buffers for running many queries against a `KDBush` or `FlatBush` without allocating.
+///
+/// Pass the same scratch to the `*_with` query variants; each call clears it, runs the query
+/// using its traversal stack, and returns the hits as a slice of its output buffer. Both buffers
+/// keep their capacity between calls, so once they've grown to fit the largest query, further
+/// queries don't allocate.
+#[derive(Clone, Debug, Default)]
+pub struct QueryScratch {
+    pub(crate) stack: Vec<usize>,
+    pub(crate) results: Vec<usize>,
+}
+
+impl QueryScratch {
+    pub fn new() -> QueryScratch {
+        QueryScratch::default()
+    }
+
+    pub fn with_capacity(stack: usize, results: usize) -> QueryScratch {
+        QueryScratch { stack: Vec::with_capacity(stack), results: Vec::with_capacity(results) }
+    }
+
+    /// The results of the most recent query.
+    pub fn results(&self) -> &[usize] {
+        &self.results
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.stack.clear();
+        self.results.clear();
+    }
+}
@@ -37,6 +37,37 @@ impl IndexVec {
         }
     }
 
+    // build the inverse of the permutation held in the first `len` entries, so that
+    // `inverse.position(self.get(i)) == Some(i)`; values that don't appear have no position
+    pub fn invert(&self, len: usize) -> IndexVec {
+        match self {
+            IndexVec::U16(v) => {
+                let bound = v[..len].iter().max().map_or(0, |m| *m as usize + 1);
+                let mut inverse = vec![u16::MAX; bound];
+                for (i, x) in v[..len].iter().enumerate() {
+                    inverse[*x as usize] = i as u16;
+                }
+                IndexVec::U16(inverse)
+            }
+            IndexVec::U32(v) => {
+                let bound = v[..len].iter().max().map_or(0, |m| *m as usize + 1);
+                let mut inverse = vec![u32::MAX; bound];
+                for (i, x) in v[..len].iter().enumerate() {
+                    inverse[*x as usize] = i as u32;
+                }
+                IndexVec::U32(inverse)
+            }
+        }
+    }
+
+    // look up a value in an inverse built by `invert`
+    pub fn position(&self, idx: usize) -> Option<usize> {
+        match self {
+            IndexVec::U16(v) => v.get(idx).filter(|x| **x != u16::MAX).map(|x| *x as usize),
+            IndexVec::U32(v) => v.get(idx).filter(|x| **x != u32::MAX).map(|x| *x as usize),
+        }
+    }
+
     #[cfg(test)]
     pub fn iter<'a>(&'a self) -> impl Iterator<Item = u32> + 'a {
         let out: Box<dyn Iterator<Item = u32>> = match self {
@@ -46,3 +77,43 @@ impl IndexVec {
         out
     }
 }
+
+// a growable bitset, used for tombstoning deleted items; it stays unallocated until the first bit
+// is set, so the common case of never deleting anything costs nothing
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BitSet {
+    words: Vec<u64>,
+    count: usize,
+}
+
+impl BitSet {
+    #[inline(always)]
+    pub fn contains(&self, idx: usize) -> bool {
+        match self.words.get(idx >> 6) {
+            Some(word) => (word >> (idx & 63)) & 1 == 1,
+            None => false,
+        }
+    }
+
+    // returns whether the bit was newly set
+    pub fn insert(&mut self, idx: usize) -> bool {
+        if self.contains(idx) {
+            return false;
+        }
+        if self.words.len() <= idx >> 6 {
+            self.words.resize((idx >> 6) + 1, 0);
+        }
+        self.words[idx >> 6] |= 1 << (idx & 63);
+        self.count += 1;
+        true
+    }
+
+    // the number of bits set
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}